@@ -1,4 +1,4 @@
-use zerocopy::{FromBytes, FromZeros, IntoBytes};
+use zerocopy::{FromBytes, FromZeros, Immutable, IntoBytes};
 
 use crate::{IeType, SmallIE};
 use core::mem::size_of;
@@ -7,7 +7,7 @@ static_assertions::assert_eq_size!(SmallIE, IEBuf);
 static_assertions::assert_eq_align!(SmallIE, IEBuf);
 
 #[repr(C)]
-#[derive(Default, Debug, Clone, Copy, PartialEq, IntoBytes, FromBytes)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, IntoBytes, FromBytes, Immutable)]
 pub struct IEBuf(pub [u8; size_of::<SmallIE>()]);
 
 impl IEBuf {
@@ -16,6 +16,7 @@ pub mod system;
 pub mod conversion_impls;
 pub mod query_impls;
 
+pub use const_default::ConstDefault;
 pub use iebuf::*;
 pub use small_ie::*;
 
@@ -1,26 +1,39 @@
 use std::num::NonZeroU8;
 
-use micrortu_build_utils::{Direction, IEType};
+use micrortu_build_utils::{bindings_size, DefaultValue, Direction, IEType, BINDINGS_BYTES_CAP};
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::{
-    braced,
+    braced, bracketed, parenthesized,
     parse::{Parse, ParseStream},
     parse_macro_input,
     punctuated::Punctuated,
-    Attribute, Ident, LitInt, Meta, MetaList, Token, Visibility,
+    token, Attribute, Ident, Lit, LitInt, Meta, MetaList, Token, Visibility,
 };
 
-use crate::state::{set_params, set_ports, should_bail_on_duplicates};
+use crate::{
+    description::{description, is_description},
+    engineering::{engineering, is_engineering, Engineering},
+    state::{set_params, set_ports, should_bail_on_duplicates},
+    warning::warning,
+};
 
 struct Port {
     attrs: Vec<Attribute>,
     mode: Ident,
     name: Ident,
     typ: IEType,
+    /// Restriction of `ANY(...)` bindings.
+    allowed_types: Vec<IEType>,
     lower_bound: usize,
     upper_bound: Option<usize>,
     optional: bool,
+    /// `[a, b, c]` after bounds, names of elements of fixed-size bindings.
+    element_names: Vec<Ident>,
+    /// `= <literal>` after `?`, with optional minus sign.
+    default: Option<(bool, Lit)>,
+    /// Plain value type after `as`.
+    view: Option<Ident>,
     errors: Vec<syn::Error>,
 }
 
@@ -31,33 +44,30 @@ impl Parse for Port {
         let name: Ident = input.parse()?;
         input.parse::<Token![:]>()?;
         let typ: Ident = input.parse()?;
-        let typ = match typ.to_string().as_str() {
-            "TI1" => IEType::TI1,
-            "TI3" => IEType::TI3,
-            "TI11" => IEType::TI11,
-            "TI13" => IEType::TI13,
-            "TI45" => IEType::TI45,
-            "TI46" => IEType::TI46,
-            "TI49" => IEType::TI49,
-            "TI50" => IEType::TI50,
-            "TI112" => IEType::TI112,
-            "TI136" => IEType::TI136,
-            "TI137" => IEType::TI137,
-            "TI138" => IEType::TI138,
-            "TI139" => IEType::TI139,
-            "TI200" => IEType::TI200,
-            "TI201" => IEType::TI201,
-            "TI202" => IEType::TI202,
-            "TI203" => IEType::TI203,
-            _ => {
-                errors.push(syn::Error::new(
-                    typ.span(),
-                    "Unknown type. Supported types are TI1, TI3, TI11, TI13, TI45, TI46, TI49, TI50, TI112, TI136, TI137, TI138, TI139, TI200, TI201, TI202, TI203",
-                    ));
-                IEType::TI1
+        let typ = ie_type(&typ).unwrap_or_else(|| {
+            errors.push(syn::Error::new(
+                typ.span(),
+                "Unknown type. Supported types are ANY, TI1, TI3, TI11, TI13, TI45, TI46, TI49, TI50, TI112, TI136, TI137, TI138, TI139, TI200, TI201, TI202, TI203",
+            ));
+            IEType::TI1
+        });
+        let mut allowed_types = vec![];
+        if typ == IEType::Any && input.peek(token::Paren) {
+            let content;
+            parenthesized!(content in input);
+            let types = Punctuated::<Ident, Token![|]>::parse_separated_nonempty(&content)?;
+            for typ in types {
+                match ie_type(&typ) {
+                    Some(IEType::Any) | None => errors.push(syn::Error::new(
+                        typ.span(),
+                        "Unknown type. `ANY` can be restricted only to TI types",
+                    )),
+                    Some(typ) => allowed_types.push(typ),
+                }
             }
-        };
+        }
         match typ {
+            IEType::Any => {}
             IEType::TI1 => {}
             IEType::TI3 => {}
             IEType::TI11 => {}
@@ -76,6 +86,22 @@ impl Parse for Port {
             IEType::TI202 => {}
             IEType::TI203 => {}
         }
+        let mut view = None;
+        if input.parse::<Option<Token![as]>>()?.is_some() {
+            let value: Ident = input.parse()?;
+            if typ == IEType::Any {
+                errors.push(syn::Error::new(
+                    value.span(),
+                    "Value views are not supported for ANY bindings",
+                ));
+            } else if !["f32", "i64", "u64", "bool"].iter().any(|v| value == v) {
+                errors.push(syn::Error::new(
+                    value.span(),
+                    "Unknown value type. Supported types are f32, i64, u64, bool",
+                ));
+            }
+            view = Some(value);
+        }
         let mode: Ident = input.parse()?;
 
         let left: LitInt = input.parse()?;
@@ -109,24 +135,97 @@ impl Parse for Port {
             ));
         }
 
+        let mut element_names = vec![];
+        if input.peek(token::Bracket) {
+            let content;
+            let brackets = bracketed!(content in input);
+            let names = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?;
+            element_names.extend(names);
+            if upper_bound != Some(lower_bound) || lower_bound < 2 {
+                errors.push(syn::Error::new(
+                    brackets.span.join(),
+                    "Element names are allowed only for fixed-size bindings with more than one element",
+                ));
+            } else if element_names.len() != lower_bound {
+                errors.push(syn::Error::new(
+                    brackets.span.join(),
+                    format!(
+                        "Expected {lower_bound} element names, got {}",
+                        element_names.len()
+                    ),
+                ));
+            }
+            for (i, name) in element_names.iter().enumerate() {
+                if element_names[..i].contains(name) {
+                    errors.push(syn::Error::new(name.span(), "Duplicate element name"));
+                }
+            }
+        }
+
         let optional = input.parse::<Token![?]>().is_ok();
 
+        let mut default = None;
+        if let Ok(eq) = input.parse::<Token![=]>() {
+            let negative = input.parse::<Option<Token![-]>>()?.is_some();
+            default = Some((negative, input.parse()?));
+            if !optional {
+                errors.push(syn::Error::new_spanned(
+                    eq,
+                    "Default value is allowed only for optional bindings",
+                ));
+            }
+        }
+
         Ok(Self {
             attrs,
             mode,
             name,
             typ,
+            allowed_types,
             lower_bound,
             upper_bound,
             optional,
+            element_names,
+            default,
+            view,
             errors,
         })
     }
 }
 
+fn ie_type(typ: &Ident) -> Option<IEType> {
+    Some(match typ.to_string().as_str() {
+        "ANY" => IEType::Any,
+        "TI1" => IEType::TI1,
+        "TI3" => IEType::TI3,
+        "TI11" => IEType::TI11,
+        "TI13" => IEType::TI13,
+        "TI45" => IEType::TI45,
+        "TI46" => IEType::TI46,
+        "TI49" => IEType::TI49,
+        "TI50" => IEType::TI50,
+        "TI112" => IEType::TI112,
+        "TI136" => IEType::TI136,
+        "TI137" => IEType::TI137,
+        "TI138" => IEType::TI138,
+        "TI139" => IEType::TI139,
+        "TI200" => IEType::TI200,
+        "TI201" => IEType::TI201,
+        "TI202" => IEType::TI202,
+        "TI203" => IEType::TI203,
+        _ => return None,
+    })
+}
+
 struct PortsInput {
     attrs: Vec<Attribute>,
     block_names: Vec<String>,
+    /// `#[quality(...)]`, `QualityPolicy` of value setters.
+    quality: Ident,
+    /// `#[track_changes]`, generates snapshot of inputs.
+    track_changes: bool,
+    /// `#[not_zeroed(...)]`, what parser does with non-zeroed OUT bindings.
+    not_zeroed: Ident,
     visibility: Visibility,
     struct_name: Ident,
     ports: Punctuated<Port, Token![,]>,
@@ -173,6 +272,32 @@ impl Parse for PortsInput {
                 ));
             }
         };
+        let mut quality = Ident::new("propagate", proc_macro2::Span::call_site());
+        if let Some(position) = attrs.iter().position(|a| a.path().is_ident("quality")) {
+            quality = attrs.remove(position).parse_args()?;
+            if !["propagate", "good", "keep"].iter().any(|p| quality == p) {
+                return Err(syn::Error::new(
+                    quality.span(),
+                    "Unknown quality policy. Supported policies are propagate, good, keep",
+                ));
+            }
+        }
+        let mut not_zeroed = Ident::new("log", proc_macro2::Span::call_site());
+        if let Some(position) = attrs.iter().position(|a| a.path().is_ident("not_zeroed")) {
+            not_zeroed = attrs.remove(position).parse_args()?;
+            if !["error", "zero", "log"].iter().any(|p| not_zeroed == p) {
+                return Err(syn::Error::new(
+                    not_zeroed.span(),
+                    "Unknown policy. Supported policies are error, zero, log",
+                ));
+            }
+        }
+        let track_changes = attrs
+            .iter()
+            .position(|a| a.path().is_ident("track_changes"));
+        if let Some(position) = track_changes {
+            attrs.remove(position).meta.require_path_only()?;
+        }
         let visibility: Visibility = input.parse()?;
         input.parse::<Token![struct]>()?;
         let struct_name: Ident = input.parse()?;
@@ -183,6 +308,9 @@ impl Parse for PortsInput {
         Ok(Self {
             attrs,
             block_names,
+            quality,
+            track_changes: track_changes.is_some(),
+            not_zeroed,
             visibility,
             struct_name,
             ports,
@@ -195,6 +323,9 @@ pub fn bindings(input: TokenStream, is_ports: bool) -> TokenStream {
     let PortsInput {
         attrs,
         block_names,
+        quality,
+        track_changes,
+        not_zeroed,
         visibility,
         struct_name,
         ports,
@@ -204,6 +335,16 @@ pub fn bindings(input: TokenStream, is_ports: bool) -> TokenStream {
     let mut parse_blocks = vec![];
     let mut names = vec![];
     let mut meta_bindings = vec![];
+    let mut binding_names = vec![];
+    let mut accessors = vec![];
+    let mut input_qualities = vec![];
+    let mut has_views = false;
+    let mut snapshot_fields = vec![];
+    let mut snapshot_updates = vec![];
+    let mut snapshot_accessors = vec![];
+    let mut snapshot_changed = vec![];
+    let mut element_views = vec![];
+    let mut reflect_blocks = vec![];
     let mut errors = vec![];
 
     for port in ports {
@@ -212,8 +353,23 @@ pub fn bindings(input: TokenStream, is_ports: bool) -> TokenStream {
         let mode_str = port.mode.to_string();
         let is_multiple = port.lower_bound > 1 || port.upper_bound != Some(1);
         let is_optional = port.optional;
-        let attrs = &port.attrs;
+        let description = description(&port.attrs).unwrap_or_else(|err| {
+            errors.push(err);
+            String::new()
+        });
+        let Engineering {
+            unit,
+            range,
+            labels,
+        } = engineering(&port.attrs, port.typ).unwrap_or_else(|err| {
+            errors.push(err);
+            Engineering::default()
+        });
+        let attrs = port.attrs.iter();
+        let attrs = attrs.filter(|attr| !is_description(attr) && !is_engineering(attr));
+        let attrs = &attrs.collect::<Vec<_>>();
         let typ = match port.typ {
+            IEType::Any => quote! { IEBuf },
             IEType::TI1 => quote! { M_SP_NA_1 },
             IEType::TI3 => quote! { M_DP_NA_1 },
             IEType::TI11 => quote! { M_ME_NB_1 },
@@ -248,6 +404,20 @@ pub fn bindings(input: TokenStream, is_ports: bool) -> TokenStream {
         let has_out = matches!(direction, Direction::Out | Direction::InOut);
         let has_in = matches!(direction, Direction::In | Direction::InOut);
 
+        let default = port.default.as_ref().and_then(|(negative, lit)| {
+            let default = if has_out {
+                Err(syn::Error::new_spanned(
+                    lit,
+                    "Default value is allowed only for `In` bindings",
+                ))
+            } else {
+                default_value(port.typ, *negative, lit)
+            };
+            default.map_err(|err| errors.push(err)).ok()
+        });
+        // Bindings with default are never `None`.
+        let is_optional = is_optional && default.is_none();
+
         names.push(match (is_multiple, is_optional, has_out) {
             (true, true, true) => quote! { #(#attrs)* pub #name: Option<&'a mut [#typ]> },
             (true, false, true) => quote! { #(#attrs)* pub #name: &'a mut [#typ] },
@@ -263,21 +433,277 @@ pub fn bindings(input: TokenStream, is_ports: bool) -> TokenStream {
         let max_size = port
             .upper_bound
             .map_or(quote! { None }, |m| quote! { Some(#m as u8) });
-        let flags = !is_optional as u8;
+        let flags = !port.optional as u8;
 
         let name_str = name.to_string();
 
         let binding = micrortu_build_utils::Port {
             name: name_str.clone(),
             typ: port.typ,
-            description: String::new(),
+            description,
             direction,
-            required: !is_optional,
+            required: !port.optional,
             min: NonZeroU8::new(min_size).unwrap_or(NonZeroU8::MIN), // will be compiler error
             max: port.upper_bound.and_then(|m| NonZeroU8::new(m as u8)),
+            default,
+            unit,
+            range,
+            labels,
+            allowed_types: port.allowed_types.clone(),
+            element_names: port.element_names.iter().map(ToString::to_string).collect(),
         };
 
         meta_bindings.push(binding);
+        binding_names.push(name.clone());
+
+        let has_qds = matches!(
+            port.typ,
+            IEType::TI11
+                | IEType::TI13
+                | IEType::TI136
+                | IEType::TI137
+                | IEType::TI138
+                | IEType::TI139
+        );
+        if let Some(range) = range.filter(|_| has_in && has_qds) {
+            let (min, max) = (range.min, range.max);
+            let accessor = Ident::new(&format!("{name}_checked"), name.span());
+            let doc = format!(" `{name}` with IV and OV set when outside of `{min}..={max}`.");
+            let check =
+                quote! { |v: &#typ| ::micrortu_sdk::range::RangeCheck::checked(*v, #min, #max) };
+            accessors.push(match (is_multiple, is_optional) {
+                (true, true) => quote! {
+                    #[doc = #doc]
+                    pub fn #accessor(&self) -> impl Iterator<Item = #typ> + '_ {
+                        self.#name.as_deref().unwrap_or(&[]).iter().map(#check)
+                    }
+                },
+                (true, false) => quote! {
+                    #[doc = #doc]
+                    pub fn #accessor(&self) -> impl Iterator<Item = #typ> + '_ {
+                        self.#name.iter().map(#check)
+                    }
+                },
+                (false, true) => quote! {
+                    #[doc = #doc]
+                    pub fn #accessor(&self) -> Option<#typ> {
+                        self.#name.as_deref().map(#check)
+                    }
+                },
+                (false, false) => quote! {
+                    #[doc = #doc]
+                    pub fn #accessor(&self) -> #typ {
+                        (#check)(&*self.#name)
+                    }
+                },
+            });
+        }
+
+        if let Some(view) = &port.view {
+            has_views = true;
+            let read = quote! { |v: &#typ| ::micrortu_sdk::value::read::<#typ, #view>(*v) };
+            let write = quote! { ::micrortu_sdk::value::write };
+            let merge = quote! { ::micrortu_sdk::value::merge_quality };
+            let setter = Ident::new(&format!("set_{name}"), name.span());
+            let policy = quote! { Self::QUALITY_POLICY.output_quality(|| self.quality()) };
+            let getter_doc = format!(" Value of `{name}` as `{view}`.");
+            let setter_doc = format!(" Updates `{name}` with `{view}` value.");
+            if has_in {
+                accessors.push(match (is_multiple, is_optional) {
+                    (true, true) => quote! {
+                        #[doc = #getter_doc]
+                        pub fn #name(&self) -> impl Iterator<Item = #view> + '_ {
+                            self.#name.as_deref().unwrap_or(&[]).iter().map(#read)
+                        }
+                    },
+                    (true, false) => quote! {
+                        #[doc = #getter_doc]
+                        pub fn #name(&self) -> impl Iterator<Item = #view> + '_ {
+                            self.#name.iter().map(#read)
+                        }
+                    },
+                    (false, true) => quote! {
+                        #[doc = #getter_doc]
+                        pub fn #name(&self) -> Option<#view> {
+                            self.#name.as_deref().map(#read)
+                        }
+                    },
+                    (false, false) => quote! {
+                        #[doc = #getter_doc]
+                        pub fn #name(&self) -> #view {
+                            (#read)(&*self.#name)
+                        }
+                    },
+                });
+                input_qualities.push(match (is_multiple, is_optional) {
+                    (true, true) => quote! {
+                        for ie in self.#name.as_deref().unwrap_or(&[]) {
+                            #merge(&mut quality, *ie);
+                        }
+                    },
+                    (true, false) => quote! {
+                        for ie in self.#name.iter() {
+                            #merge(&mut quality, *ie);
+                        }
+                    },
+                    (false, true) => quote! {
+                        if let Some(ie) = self.#name.as_deref() {
+                            #merge(&mut quality, *ie);
+                        }
+                    },
+                    (false, false) => quote! { #merge(&mut quality, *self.#name); },
+                });
+            }
+            if has_out {
+                accessors.push(match (is_multiple, is_optional) {
+                    (true, true) => quote! {
+                        #[doc = #setter_doc]
+                        pub fn #setter(&mut self, values: impl IntoIterator<Item = #view>) {
+                            let quality = #policy;
+                            let target = self.#name.as_deref_mut().unwrap_or(&mut []);
+                            for (ie, value) in target.iter_mut().zip(values) {
+                                #write(ie, value, quality);
+                            }
+                        }
+                    },
+                    (true, false) => quote! {
+                        #[doc = #setter_doc]
+                        pub fn #setter(&mut self, values: impl IntoIterator<Item = #view>) {
+                            let quality = #policy;
+                            for (ie, value) in self.#name.iter_mut().zip(values) {
+                                #write(ie, value, quality);
+                            }
+                        }
+                    },
+                    (false, true) => quote! {
+                        #[doc = #setter_doc]
+                        pub fn #setter(&mut self, value: #view) {
+                            let quality = #policy;
+                            if let Some(ie) = self.#name.as_deref_mut() {
+                                #write(ie, value, quality);
+                            }
+                        }
+                    },
+                    (false, false) => quote! {
+                        #[doc = #setter_doc]
+                        pub fn #setter(&mut self, value: #view) {
+                            let quality = #policy;
+                            #write(&mut *self.#name, value, quality);
+                        }
+                    },
+                });
+            }
+        }
+
+        if !port.element_names.is_empty() {
+            let elements_name = format!("{struct_name}{}", camel_case(&name_str));
+            let elements_name = Ident::new(&elements_name, name.span());
+            let elements = &port.element_names;
+            let doc = format!(" Named elements of `{struct_name}::{name}`.");
+            let getter = Ident::new(&format!("{name}_elements"), name.span());
+            let getter_doc = format!(" Elements of `{name}` by name.");
+            element_views.push(quote! {
+                #[doc = #doc]
+                #[derive(Debug, Clone, Copy, PartialEq)]
+                #visibility struct #elements_name<R> {
+                    #(pub #elements: R,)*
+                }
+            });
+            // Parser guarantees that fixed-size bindings have exactly that many elements.
+            let split = |slice: proc_macro2::TokenStream| {
+                quote! {
+                    let [#(#elements),*] = #slice else {
+                        unreachable!("Fixed-size binding has wrong number of elements")
+                    };
+                    #elements_name { #(#elements),* }
+                }
+            };
+            accessors.push(if is_optional {
+                let split = split(quote! { elements });
+                quote! {
+                    #[doc = #getter_doc]
+                    pub fn #getter(&self) -> Option<#elements_name<&#typ>> {
+                        let elements = self.#name.as_deref()?;
+                        Some({ #split })
+                    }
+                }
+            } else {
+                let split = split(quote! { &self.#name[..] });
+                quote! {
+                    #[doc = #getter_doc]
+                    pub fn #getter(&self) -> #elements_name<&#typ> {
+                        #split
+                    }
+                }
+            });
+            if has_out {
+                let getter = Ident::new(&format!("{name}_elements_mut"), name.span());
+                let getter_doc = format!(" Mutable elements of `{name}` by name.");
+                accessors.push(if is_optional {
+                    let split = split(quote! { elements });
+                    quote! {
+                        #[doc = #getter_doc]
+                        pub fn #getter(&mut self) -> Option<#elements_name<&mut #typ>> {
+                            let elements = self.#name.as_deref_mut()?;
+                            Some({ #split })
+                        }
+                    }
+                } else {
+                    let split = split(quote! { &mut self.#name[..] });
+                    quote! {
+                        #[doc = #getter_doc]
+                        pub fn #getter(&mut self) -> #elements_name<&mut #typ> {
+                            #split
+                        }
+                    }
+                });
+            }
+        }
+
+        if track_changes && has_in {
+            // Unbounded bindings can't have more elements than fit into a header.
+            let capacity = port
+                .upper_bound
+                .unwrap_or(usize::from(u8::MAX) / port.typ.size());
+            let current = match (is_multiple, is_optional) {
+                (true, true) => quote! { ports.#name.as_deref().unwrap_or(&[]) },
+                (true, false) => quote! { &ports.#name[..] },
+                (false, true) => {
+                    quote! { ports.#name.as_deref().map_or(&[][..], ::core::slice::from_ref) }
+                }
+                (false, false) => quote! { ::core::slice::from_ref(&*ports.#name) },
+            };
+            let changed = Ident::new(&format!("{name}_changed"), name.span());
+            let previous = Ident::new(&format!("{name}_previous"), name.span());
+            let changed_doc = format!(" Whether `{name}` differs from its value on last update.");
+            let previous_doc = format!(" Value of `{name}` on last update.");
+            snapshot_fields
+                .push(quote! { #name: ::micrortu_sdk::changes::Previous<#typ, #capacity> });
+            snapshot_updates.push(quote! { self.#name.update(#current); });
+            snapshot_changed.push(changed.clone());
+            let previous = if is_multiple {
+                quote! {
+                    #[doc = #previous_doc]
+                    pub fn #previous(&self) -> Option<&[#typ]> {
+                        self.#name.get()
+                    }
+                }
+            } else {
+                quote! {
+                    #[doc = #previous_doc]
+                    pub fn #previous(&self) -> Option<#typ> {
+                        self.#name.get()?.first().copied()
+                    }
+                }
+            };
+            snapshot_accessors.push(quote! {
+                #[doc = #changed_doc]
+                pub fn #changed(&self, ports: &#struct_name<'_>) -> bool {
+                    self.#name.changed(#current)
+                }
+                #previous
+            });
+        }
 
         let to_nonzero_max_size = port.upper_bound.map_or(0, |m| m as u8);
         let direction_quote = match direction {
@@ -288,18 +714,27 @@ pub fn bindings(input: TokenStream, is_ports: bool) -> TokenStream {
 
         let port_typ = port.typ as u8;
         report_blocks.push(quote! { {
-            #[cfg(not(target_arch = "wasm32"))] {
-                const NAME: &str = stringify!(#name);
-                ::micrortu_sdk::NativeBindingDefinition::<'static> {
-                    name: &NAME,
-                    flags: #flags,
-                    typ: #port_typ,
-                    min_size: #min_size,
-                    max_size: ::core::num::NonZeroU8::new(#to_nonzero_max_size),
-                    direction: #direction_quote,
-                }
-            } }
-        });
+            const NAME: &str = stringify!(#name);
+            ::micrortu_sdk::NativeBindingDefinition::<'static> {
+                name: &NAME,
+                flags: #flags,
+                typ: #port_typ,
+                min_size: #min_size,
+                max_size: ::core::num::NonZeroU8::new(#to_nonzero_max_size),
+                direction: #direction_quote,
+            }
+        } });
+
+        let index = reflect_blocks.len();
+        // Bindings that exceed `BINDINGS_BYTES_CAP` are rejected, so index fits into `u8`.
+        let binding = index as u8;
+        let as_bytes = quote! { ::zerocopy::IntoBytes::as_bytes };
+        let bytes = match (is_multiple, is_optional) {
+            (true, true) => quote! { #as_bytes(self.#name.as_deref().unwrap_or(&[])) },
+            (false, true) => quote! { self.#name.as_deref().map_or(&[][..], #as_bytes) },
+            (_, false) => quote! { #as_bytes(&*self.#name) },
+        };
+        reflect_blocks.push(quote! { #index => #bytes, });
 
         let ret = match (is_multiple, is_optional) {
             (true, true) => quote! { Some(&mut value[..]) },
@@ -307,6 +742,67 @@ pub fn bindings(input: TokenStream, is_ports: bool) -> TokenStream {
             (false, true) => quote! { Some(&mut value[0]) },
             (false, false) => quote! { &mut value[0] },
         };
+        // Elements of `ANY` inputs must be tagged with one of allowed types.
+        let check_any = if port.typ == IEType::Any && has_in {
+            let allowed = port.allowed_types.iter().map(|typ| *typ as u8);
+            quote! {
+                let allowed: &[u8] = &[#(#allowed),*];
+                let is_allowed = |ie: &::micrortu_sdk::ie_base::IEBuf| {
+                    ie.is_valid() && (allowed.is_empty() || allowed.contains(&ie.0[0]))
+                };
+                if !value.iter().all(is_allowed) {
+                    ::micrortu_sdk::error!("Got element of unexpected type");
+                    return Err(::micrortu_sdk::ParseError::InvalidElements { binding: #binding });
+                }
+            }
+        } else {
+            quote! {}
+        };
+        let check_zeroed = if has_in {
+            quote! {}
+        } else {
+            let on_dirty = match not_zeroed.to_string().as_str() {
+                "error" => quote! {
+                    return Err(::micrortu_sdk::ParseError::NotZeroed { binding: #binding });
+                },
+                "zero" => quote! { data.fill(0); },
+                _ => quote! {
+                    static LOGGED: ::core::sync::atomic::AtomicBool =
+                        ::core::sync::atomic::AtomicBool::new(false);
+                    if !LOGGED.swap(true, ::core::sync::atomic::Ordering::Relaxed) {
+                        ::micrortu_sdk::warn!("OUT binding `{}` is not zeroed", #name_str);
+                    }
+                    data.fill(0);
+                },
+            };
+            quote! {
+                if data.iter().any(|&b| b != 0) {
+                    #on_dirty
+                }
+            }
+        };
+        // Unbound optional ports are passed with zero elements, the same rule
+        // `micrortu_sdk::parse_port` applies to `IEBuf` bindings.
+        let unbound = match default {
+            Some(default) => {
+                let default = default_tokens(&typ, default);
+                // Multiple bindings get as many elements as they can hold,
+                // so fixed-size ones keep their size.
+                let default = if is_multiple {
+                    let len = port.upper_bound.unwrap_or(port.lower_bound);
+                    quote! {
+                        const ELEMENT: #typ = #default;
+                        static DEFAULT: [#typ; #len] = [ELEMENT; #len];
+                        &DEFAULT[..]
+                    }
+                } else {
+                    quote! { static DEFAULT: #typ = #default; &DEFAULT }
+                };
+                quote! { if len == 0 { #default } else }
+            }
+            None if is_optional => quote! { if len == 0 { None } else },
+            None => quote! {},
+        };
 
         parse_blocks.push(quote! {#name: {
             let (pad_len, rest) = header.split_at_mut(2);
@@ -316,25 +812,30 @@ pub fn bindings(input: TokenStream, is_ports: bool) -> TokenStream {
             let len = bytes / ::core::mem::size_of::<#typ>();
             let (data, rest) = source.split_at_mut(pad + bytes);
             source = rest;
+            #unbound {
             if len < #min_size as usize {
                 ::micrortu_sdk::error!("Got {} elements, expected at least {}", len, #min_size);
-                return Err(::micrortu_sdk::ParseError::NotEnoughData);
+                return Err(::micrortu_sdk::ParseError::NotEnoughElements { binding: #binding });
             }
             if #max_size.map_or(false, |m: u8| len > m as usize) {
                 let max_size: u8 = #max_size.unwrap();
                 ::micrortu_sdk::error!("Got {} elements, expected at most {}", len, max_size);
-                return Err(::micrortu_sdk::ParseError::TooMuchData);
-            }
-            if !#has_in {
-                assert!(data.iter().all(|&b| b == 0), "OUT port/param is not zeroed");
+                return Err(::micrortu_sdk::ParseError::TooManyElements { binding: #binding });
             }
+            #check_zeroed
             let value = <[#typ] as ::zerocopy::FromBytes>::mut_from_bytes(&mut data[pad..]);
-            let mut value = value.map_err(|_| ::micrortu_sdk::ParseError::InvalidData)?;
+            let mut value = value.map_err(|_| {
+                ::micrortu_sdk::ParseError::InvalidElements { binding: #binding }
+            })?;
+            #check_any
             #ret
+            }
           },
         });
     }
 
+    let warnings = check_layout(&struct_name, &binding_names, &meta_bindings, &mut errors);
+
     for block_name in block_names {
         let res = if is_ports {
             set_ports(&block_name, meta_bindings.clone())
@@ -366,15 +867,7 @@ pub fn bindings(input: TokenStream, is_ports: bool) -> TokenStream {
         fn parse(mut source: &'a mut [u8]) -> Self {
             match Self::parse_fallible(source) {
                 Ok(binds) => binds,
-                Err(err) => {
-                    ::micrortu_sdk::error!("Failed to parse bindings: {:?}", err);
-                    #[cfg(target_arch = "wasm32")] {
-                        ::core::arch::wasm32::unreachable()
-                    }
-                    #[cfg(not(target_arch = "wasm32"))] {
-                        panic!("Failed to parse bindings: {:?}", err)
-                    }
-                }
+                Err(err) => ::micrortu_sdk::parse_failed(err),
             }
         }
     };
@@ -382,11 +875,89 @@ pub fn bindings(input: TokenStream, is_ports: bool) -> TokenStream {
     let report = quote! {
         #[cfg(not(target_arch = "wasm32"))]
         fn report() -> &'static [::micrortu_sdk::NativeBindingDefinition<'static>] {
-            const BINDINGS: &[::micrortu_sdk::NativeBindingDefinition<'static>] = &[
+            <Self as ::micrortu_sdk::reflect::Reflect>::DEFINITIONS
+        }
+    };
+
+    let binding_body = if reflect_blocks.is_empty() {
+        quote! {
+            let _ = index;
+            None
+        }
+    } else {
+        quote! {
+            let bytes: &[u8] = match index {
+                #(#reflect_blocks)*
+                _ => return None,
+            };
+            ::micrortu_sdk::BindingView::new(Self::DEFINITIONS.get(index)?, bytes)
+        }
+    };
+    let reflect = quote! {
+        impl ::micrortu_sdk::reflect::Reflect for #struct_name<'_> {
+            const DEFINITIONS: &'static [::micrortu_sdk::NativeBindingDefinition<'static>] = &[
                 #(#report_blocks,)*
             ];
-            BINDINGS
+
+            fn binding(&self, index: usize) -> Option<::micrortu_sdk::BindingView<'_>> {
+                #binding_body
+            }
+        }
+    };
+
+    let quality_fn = if has_views {
+        let policy = match quality.to_string().as_str() {
+            "good" => quote! { Good },
+            "keep" => quote! { Keep },
+            _ => quote! { Propagate },
+        };
+        quote! {
+            /// Quality of outputs written by value setters, from `#[quality(...)]`.
+            pub const QUALITY_POLICY: ::micrortu_sdk::value::QualityPolicy =
+                ::micrortu_sdk::value::QualityPolicy::#policy;
+
+            /// Combined quality of inputs with value views.
+            pub fn quality(&self) -> ::micrortu_sdk::ie_base::QDS {
+                let mut quality = ::micrortu_sdk::ie_base::QDS::default();
+                #(#input_qualities)*
+                quality
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let snapshot = if track_changes {
+        let snapshot_name = Ident::new(&format!("{struct_name}Snapshot"), struct_name.span());
+        let doc = format!(" Inputs of [`{struct_name}`] on last update, for change detection.");
+        quote! {
+            #[doc = #doc]
+            #[derive(Debug, Clone, Copy, Default)]
+            #visibility struct #snapshot_name {
+                #(#snapshot_fields,)*
+            }
+
+            impl #snapshot_name {
+                #[must_use]
+                pub fn new() -> Self {
+                    Self::default()
+                }
+
+                /// Stores current values of inputs. Call it at the end of step.
+                pub fn update(&mut self, ports: &#struct_name<'_>) {
+                    #(#snapshot_updates)*
+                }
+
+                /// Whether any input differs from its value on last update.
+                pub fn changed(&self, ports: &#struct_name<'_>) -> bool {
+                    false #(|| self.#snapshot_changed(ports))*
+                }
+
+                #(#snapshot_accessors)*
+            }
         }
+    } else {
+        quote! {}
     };
 
     let impl_comment = format!(
@@ -398,6 +969,7 @@ pub fn bindings(input: TokenStream, is_ports: bool) -> TokenStream {
 
     let expanded = quote! {
         #(#errors)*
+        #(#warnings)*
 
         #impl_doc_comment
         #(#attrs)*
@@ -411,7 +983,217 @@ pub fn bindings(input: TokenStream, is_ports: bool) -> TokenStream {
             #parse
             #report
         }
+
+        #reflect
+
+        #snapshot
+        #(#element_views)*
+
+        impl #struct_name<'_> {
+            #quality_fn
+            #(#accessors)*
+        }
     };
 
     expanded.into()
 }
+
+/// `snake_case` to `CamelCase`, for names of generated types.
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|c| c.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+        })
+        .collect()
+}
+
+/// Checks that bindings fit into `BINDINGS_BYTES_CAP` with maximum number of
+/// elements. Returns warnings with effective maximum of unbounded bindings.
+fn check_layout(
+    struct_name: &Ident,
+    names: &[Ident],
+    bindings: &[micrortu_build_utils::Port],
+    errors: &mut Vec<syn::Error>,
+) -> Vec<proc_macro2::TokenStream> {
+    let total = bindings_size(bindings);
+    if total > BINDINGS_BYTES_CAP {
+        errors.push(syn::Error::new_spanned(
+            struct_name,
+            format!(
+                "Bindings take up to {total} bytes, but only {BINDINGS_BYTES_CAP} bytes are available"
+            ),
+        ));
+    }
+
+    let mut warnings = vec![];
+    for (name, port) in names.iter().zip(bindings) {
+        let size = port.typ.size();
+        if let Some(max) = port.max {
+            let bytes = size * usize::from(max.get());
+            if bytes > u8::MAX.into() {
+                errors.push(syn::Error::new_spanned(
+                    name,
+                    format!("Binding takes up to {bytes} bytes, but at most 255 bytes are allowed"),
+                ));
+            }
+            continue;
+        }
+        // Space left when all other bindings take their maximum.
+        let others = total - port.payload_size(port.min.get().into());
+        let available = BINDINGS_BYTES_CAP.saturating_sub(others + port.typ.align() - 1);
+        let max = (available / size).min(usize::from(u8::MAX) / size);
+        let note = format!(
+            "Binding `{name}` is unbounded, at most {max} elements fit into bindings. \
+             Consider setting maximum size explicitly."
+        );
+        warnings.push(warning(name, &note));
+    }
+    warnings
+}
+
+/// Checks that `lit` is a valid default value for bindings of type `typ`.
+fn default_value(typ: IEType, negative: bool, lit: &Lit) -> syn::Result<DefaultValue> {
+    let invalid = |message: &str| Err(syn::Error::new_spanned(lit, message));
+    let signed = |min: i128, max: i128| -> syn::Result<DefaultValue> {
+        let Lit::Int(int) = lit else {
+            return invalid("Expected integer default value");
+        };
+        let value = int.base10_parse::<i128>()?;
+        let value = if negative { -value } else { value };
+        if !(min..=max).contains(&value) {
+            return invalid("Default value is out of range for binding type");
+        }
+        Ok(DefaultValue::Int(value as i64))
+    };
+    let unsigned = |max: u128| -> syn::Result<DefaultValue> {
+        let Lit::Int(int) = lit else {
+            return invalid("Expected integer default value");
+        };
+        let value = int.base10_parse::<u128>()?;
+        if negative || value > max {
+            return invalid("Default value is out of range for binding type");
+        }
+        Ok(DefaultValue::UInt(value as u64))
+    };
+
+    match typ {
+        IEType::TI1 => match lit {
+            Lit::Bool(value) if !negative => Ok(DefaultValue::Bool(value.value)),
+            _ => invalid("Expected `true` or `false` default value"),
+        },
+        IEType::TI13 | IEType::TI50 | IEType::TI112 => {
+            let value = match lit {
+                Lit::Float(float) => float.base10_parse::<f32>()?,
+                Lit::Int(int) => int.base10_parse::<f32>()?,
+                _ => return invalid("Expected numeric default value"),
+            };
+            if !value.is_finite() {
+                return invalid("Default value is out of range for binding type");
+            }
+            Ok(DefaultValue::Float(f64::from(if negative {
+                -value
+            } else {
+                value
+            })))
+        }
+        IEType::TI11 | IEType::TI49 => signed(i16::MIN.into(), i16::MAX.into()),
+        IEType::TI137 | IEType::TI201 => signed(i32::MIN.into(), i32::MAX.into()),
+        IEType::TI139 | IEType::TI203 => signed(i64::MIN.into(), i64::MAX.into()),
+        IEType::TI136 | IEType::TI200 => unsigned(u32::MAX.into()),
+        IEType::TI138 | IEType::TI202 => unsigned(u64::MAX.into()),
+        IEType::Any | IEType::TI3 | IEType::TI45 | IEType::TI46 => {
+            invalid("Default values are not supported for that binding type")
+        }
+    }
+}
+
+/// Const expression of type `typ` with `default` value.
+fn default_tokens(
+    typ: &proc_macro2::TokenStream,
+    default: DefaultValue,
+) -> proc_macro2::TokenStream {
+    let value = match default {
+        DefaultValue::Bool(value) => {
+            let raw = u8::from(value);
+            return quote! {
+                #typ {
+                    value: ::micrortu_sdk::ie_base::SIQ {
+                        raw: ::micrortu_sdk::ie_base::RawQualityDescriptor(#raw),
+                    },
+                }
+            };
+        }
+        DefaultValue::Int(value) => proc_macro2::Literal::i64_unsuffixed(value),
+        DefaultValue::UInt(value) => proc_macro2::Literal::u64_unsuffixed(value),
+        DefaultValue::Float(value) => proc_macro2::Literal::f32_suffixed(value as f32),
+    };
+    quote! {
+        #typ {
+            value: #value,
+            ..<#typ as ::micrortu_sdk::ie_base::ConstDefault>::DEFAULT
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU8;
+
+    use micrortu_build_utils::{Direction, IEType, Port};
+    use proc_macro2::Span;
+    use syn::Ident;
+
+    use super::{camel_case, check_layout};
+
+    fn port(max: Option<u8>) -> Port {
+        Port {
+            name: String::new(),
+            typ: IEType::TI13,
+            description: String::new(),
+            direction: Direction::In,
+            required: true,
+            min: NonZeroU8::MIN,
+            max: max.and_then(NonZeroU8::new),
+            default: None,
+            unit: None,
+            range: None,
+            labels: None,
+            allowed_types: vec![],
+            element_names: vec![],
+        }
+    }
+
+    fn check(bindings: &[Port]) -> (usize, usize) {
+        let ident = |name: &str| Ident::new(name, Span::call_site());
+        let names: Vec<_> = (0..bindings.len())
+            .map(|i| ident(&format!("p{i}")))
+            .collect();
+        let mut errors = vec![];
+        let warnings = check_layout(&ident("Ports"), &names, bindings, &mut errors);
+        (errors.len(), warnings.len())
+    }
+
+    #[test]
+    fn layout_fits_into_cap() {
+        assert_eq!(check(&[port(Some(50)), port(Some(50))]), (0, 0));
+        // 52 elements of TI13 take more than 255 bytes.
+        assert_eq!(check(&[port(Some(52))]), (1, 0));
+        // 3 * (2 + 250) bytes is more than 512.
+        assert_eq!(
+            check(&[port(Some(50)), port(Some(50)), port(Some(50))]),
+            (1, 0)
+        );
+        assert_eq!(check(&[port(None), port(Some(1))]), (0, 1));
+    }
+
+    #[test]
+    fn camel_case_names() {
+        assert_eq!(camel_case("u"), "U");
+        assert_eq!(camel_case("phase_voltage"), "PhaseVoltage");
+    }
+}
@@ -0,0 +1,160 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{
+    parse_macro_input, punctuated::Punctuated, DeriveInput, Expr, ExprLit, Ident, Lit, Meta, Token,
+};
+
+use crate::{description::description, register_block};
+
+pub fn derive_block(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let block_type = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "Block can't have generic parameters",
+        ));
+    }
+
+    let mut block_names = vec![];
+    let mut capacity = 1;
+    let mut hooks = vec![];
+    let mut versions = register_block::BlockVersions::default();
+    for attr in &input.attrs {
+        match &attr.meta {
+            Meta::List(_) if attr.path().is_ident("block_names") => {
+                let names =
+                    attr.parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated)?;
+                block_names.extend(names);
+            }
+            Meta::List(_) if attr.path().is_ident("hooks") => {
+                let names =
+                    attr.parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated)?;
+                for name in names {
+                    if name != "reset" && name != "reconfigure" {
+                        return Err(syn::Error::new_spanned(
+                            name,
+                            "Unknown hook. Supported: reset, reconfigure",
+                        ));
+                    }
+                    hooks.push(name);
+                }
+            }
+            Meta::NameValue(it) if it.path.is_ident("capacity") => match &it.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Int(lit), ..
+                }) => capacity = register_block::parse_capacity(lit)?,
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        attr,
+                        "Invalid attribute value. It must be of form #[capacity = <int>]",
+                    ))
+                }
+            },
+            Meta::NameValue(it) if it.path.is_ident("version") || it.path.is_ident("firmware") => {
+                let key = it.path.require_ident()?.to_string();
+                let Expr::Lit(ExprLit {
+                    lit: Lit::Str(lit), ..
+                }) = &it.value
+                else {
+                    return Err(syn::Error::new_spanned(
+                        attr,
+                        format!("Invalid attribute value. It must be of form #[{key} = \"...\"]"),
+                    ));
+                };
+                versions.set(&key, lit)?;
+            }
+            _ => (),
+        }
+    }
+    if block_names.is_empty() {
+        return Err(syn::Error::new_spanned(
+            block_type,
+            "Block must have #[block_names(...)] attribute",
+        ));
+    }
+
+    let mut output = quote! {};
+    for block_name in &block_names {
+        let init_fn = Ident::new(&format!("_init_{block_name}"), block_name.span());
+        let step_fn = Ident::new(&format!("_step_{block_name}"), block_name.span());
+        let reset_fn = Ident::new(&format!("_reset_{block_name}"), block_name.span());
+        let reconfigure_fn = Ident::new(&format!("_reconfigure_{block_name}"), block_name.span());
+        let has_reset = hooks.iter().any(|hook| hook == "reset");
+        let has_reconfigure = hooks.iter().any(|hook| hook == "reconfigure");
+
+        let create = quote! { ::micrortu_sdk::block::factory::<#block_type>(input) };
+        let deinit = quote! { ::micrortu_sdk::block::deinit(block); };
+        let (storage, factory_fn, deinit_fn) =
+            register_block::pooled(block_type, block_name, capacity, &create, Some(&deinit));
+
+        let fns = register_block::BlockFns {
+            factory: factory_fn,
+            init: init_fn.clone(),
+            step: step_fn.clone(),
+            deinit: Some(deinit_fn),
+            reset: has_reset.then(|| reset_fn.clone()),
+            reconfigure: has_reconfigure.then(|| reconfigure_fn.clone()),
+        };
+        let registered = register_block::expand(
+            block_type,
+            block_name,
+            description(&input.attrs)?,
+            &versions,
+            &fns,
+        )?;
+
+        let reset = has_reset.then(|| {
+            quote! {
+                #[doc(hidden)]
+                fn #reset_fn(
+                    shared: &mut ::micrortu_sdk::Shared,
+                    block: &mut #block_type,
+                ) -> ::micrortu_sdk::StepResult {
+                    ::micrortu_sdk::block::reset(shared, block)
+                }
+            }
+        });
+        let reconfigure = has_reconfigure.then(|| {
+            quote! {
+                #[doc(hidden)]
+                fn #reconfigure_fn(
+                    input: &::micrortu_sdk::FactoryInput,
+                    block: &mut #block_type,
+                ) -> ::micrortu_sdk::StepResult {
+                    ::micrortu_sdk::block::reconfigure(input, block)
+                }
+            }
+        });
+
+        output.extend(quote! {
+            #storage
+            #[doc(hidden)]
+            fn #init_fn(
+                shared: &mut ::micrortu_sdk::Shared,
+                block: &mut #block_type,
+            ) -> ::micrortu_sdk::StepResult {
+                ::micrortu_sdk::block::init(shared, block)
+            }
+            #[doc(hidden)]
+            fn #step_fn(
+                shared: &mut ::micrortu_sdk::Shared,
+                block: &mut #block_type,
+            ) -> ::micrortu_sdk::StepResult {
+                ::micrortu_sdk::block::step(shared, block)
+            }
+            #reset
+            #reconfigure
+
+            #registered
+        });
+    }
+
+    Ok(output)
+}
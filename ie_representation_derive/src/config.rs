@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use micrortu_build_utils::{AllowedType, BlockConf};
 use proc_macro::TokenStream;
 use quote::quote;
@@ -5,6 +7,7 @@ use syn::{parse_macro_input, Data, DataStruct, DeriveInput, Fields, Meta, Type};
 
 use crate::{
     bindings::parse_block_names,
+    description::description,
     state::{set_block_conf, should_bail_on_duplicates},
 };
 
@@ -42,19 +45,27 @@ pub fn derive_config(input: TokenStream) -> TokenStream {
     }
     let name = input.ident;
 
+    let mut descriptions = BTreeMap::new();
     let fields = match input.data {
         Data::Struct(DataStruct {
             fields: Fields::Named(fields),
             ..
-        }) => fields
-            .named
-            .into_iter()
-            .map(|f| {
+        }) => {
+            let mut mapped = vec![];
+            for f in fields.named {
                 let field_name = f.ident.unwrap().to_string();
                 let field_type = map_type_to_allowed(&f.ty);
-                (field_name, field_type)
-            })
-            .collect(),
+                match description(&f.attrs) {
+                    Ok(text) if text.is_empty() => {}
+                    Ok(text) => {
+                        descriptions.insert(field_name.clone(), text);
+                    }
+                    Err(err) => return err.to_compile_error().into(),
+                }
+                mapped.push((field_name, field_type));
+            }
+            mapped
+        }
         _ => {
             return syn::Error::new_spanned(
                 name,
@@ -65,7 +76,11 @@ pub fn derive_config(input: TokenStream) -> TokenStream {
         }
     };
 
-    let block_conf = BlockConf { required, fields };
+    let block_conf = BlockConf {
+        required,
+        fields,
+        descriptions,
+    };
 
     for block_name in block_names {
         let res = set_block_conf(&block_name, block_conf.clone());
@@ -95,6 +110,10 @@ pub fn derive_config(input: TokenStream) -> TokenStream {
         };
         quote! { (#field_name.into(), #field_type_ident) }
     });
+    let baked_in_descriptions = block_conf
+        .descriptions
+        .iter()
+        .map(|(field_name, text)| quote! { (#field_name.into(), #text.into()) });
 
     let baked_in = if is_native {
         quote! {
@@ -103,6 +122,7 @@ pub fn derive_config(input: TokenStream) -> TokenStream {
                 ::micrortu_build_utils::BlockConf {
                     required: #required,
                     fields: vec![#(#baked_in),*],
+                    descriptions: [#(#baked_in_descriptions),*].into_iter().collect(),
                 }
             }
         }
@@ -0,0 +1,74 @@
+use syn::{Attribute, Expr, ExprLit, Lit, Meta};
+
+/// Description for metadata, taken from `#[description = "..."]` if present,
+/// or from `///` doc comments otherwise.
+pub fn description(attrs: &[Attribute]) -> syn::Result<String> {
+    let mut docs = vec![];
+    for attr in attrs {
+        let Meta::NameValue(it) = &attr.meta else {
+            continue;
+        };
+        let is_doc = it.path.is_ident("doc");
+        if !is_doc && !is_description(attr) {
+            continue;
+        }
+        let Expr::Lit(ExprLit {
+            lit: Lit::Str(value),
+            ..
+        }) = &it.value
+        else {
+            if is_doc {
+                continue;
+            }
+            return Err(syn::Error::new_spanned(
+                attr,
+                "Invalid attribute value. It must be of form #[description = \"...\"]",
+            ));
+        };
+        if !is_doc {
+            return Ok(value.value());
+        }
+        docs.push(value.value());
+    }
+
+    let lines = docs.iter().flat_map(|doc| doc.split('\n'));
+    let lines: Vec<_> = lines
+        .map(|line| line.strip_prefix(' ').unwrap_or(line))
+        .collect();
+    Ok(lines.join("\n").trim().to_string())
+}
+
+/// Whether `attr` is `#[description = "..."]`, which is not a real attribute
+/// and must not be emitted.
+pub fn is_description(attr: &Attribute) -> bool {
+    attr.path().is_ident("description")
+}
+
+#[cfg(test)]
+mod test {
+    use syn::{parse_quote, Attribute};
+
+    use super::description;
+
+    #[test]
+    fn docs_and_explicit() {
+        let attrs: Vec<Attribute> = parse_quote! {
+            /// Setpoint of the regulator.
+            ///
+            ///  Indented.
+            #[allow(dead_code)]
+        };
+        let expected = "Setpoint of the regulator.\n\n Indented.";
+        assert_eq!(description(&attrs).unwrap(), expected);
+
+        let attrs: Vec<Attribute> = parse_quote! {
+            /// Internal note.
+            #[description = "Setpoint"]
+        };
+        assert_eq!(description(&attrs).unwrap(), "Setpoint");
+
+        let attrs: Vec<Attribute> = parse_quote! { #[description = 1] };
+        assert!(description(&attrs).is_err());
+        assert_eq!(description(&[]).unwrap(), "");
+    }
+}
@@ -0,0 +1,193 @@
+use micrortu_build_utils::{IEType, Labels, Range};
+use syn::{
+    punctuated::Punctuated, Attribute, Expr, ExprLit, ExprUnary, Lit, Meta, MetaNameValue, Token,
+    UnOp,
+};
+
+/// Commissioning metadata of a port: `#[unit]`, `#[range]` and `#[labels]`.
+#[derive(Default)]
+pub struct Engineering {
+    pub unit: Option<String>,
+    pub range: Option<Range>,
+    pub labels: Option<Labels>,
+}
+
+/// Parses engineering attributes of a binding of type `typ`.
+pub fn engineering(attrs: &[Attribute], typ: IEType) -> syn::Result<Engineering> {
+    let mut result = Engineering::default();
+    for attr in attrs.iter().filter(|attr| is_engineering(attr)) {
+        if attr.path().is_ident("unit") {
+            result.unit = Some(unit(attr)?);
+        } else if attr.path().is_ident("range") {
+            if !is_numeric(typ) {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "Range is allowed only for numeric bindings",
+                ));
+            }
+            result.range = Some(range(attr)?);
+        } else {
+            let states: &[_] = match typ {
+                IEType::TI1 | IEType::TI45 => &["off", "on"],
+                IEType::TI3 | IEType::TI46 => &["off", "on", "intermediate", "indeterminate"],
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        attr,
+                        "Labels are allowed only for TI1, TI3, TI45 and TI46 bindings",
+                    ))
+                }
+            };
+            result.labels = Some(labels(attr, states)?);
+        }
+    }
+    Ok(result)
+}
+
+/// Whether `attr` is one of engineering attributes, which must not be emitted.
+pub fn is_engineering(attr: &Attribute) -> bool {
+    let path = attr.path();
+    path.is_ident("unit") || path.is_ident("range") || path.is_ident("labels")
+}
+
+fn is_numeric(typ: IEType) -> bool {
+    !matches!(
+        typ,
+        IEType::Any | IEType::TI1 | IEType::TI3 | IEType::TI45 | IEType::TI46
+    )
+}
+
+fn unit(attr: &Attribute) -> syn::Result<String> {
+    match &attr.meta {
+        Meta::NameValue(MetaNameValue {
+            value:
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(unit),
+                    ..
+                }),
+            ..
+        }) if !unit.value().is_empty() => Ok(unit.value()),
+        _ => Err(syn::Error::new_spanned(
+            attr,
+            "Invalid attribute value. It must be of form #[unit = \"...\"]",
+        )),
+    }
+}
+
+fn range(attr: &Attribute) -> syn::Result<Range> {
+    let invalid = || {
+        syn::Error::new_spanned(
+            attr,
+            "Invalid attribute value. It must be of form #[range(<min>, <max>)]",
+        )
+    };
+    let bounds = attr
+        .parse_args_with(Punctuated::<Expr, Token![,]>::parse_terminated)
+        .map_err(|_| invalid())?;
+    let bounds = bounds.iter().map(number).collect::<Option<Vec<_>>>();
+    let Some([min, max]) = bounds.as_deref() else {
+        return Err(invalid());
+    };
+    if min > max {
+        return Err(syn::Error::new_spanned(
+            attr,
+            "Range minimum must be less than or equal to maximum",
+        ));
+    }
+    Ok(Range {
+        min: *min,
+        max: *max,
+    })
+}
+
+/// Numeric literal with optional minus sign.
+fn number(expr: &Expr) -> Option<f64> {
+    let value = match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Float(lit),
+            ..
+        }) => lit.base10_parse().ok(),
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(lit), ..
+        }) => lit.base10_parse().ok(),
+        Expr::Unary(ExprUnary {
+            op: UnOp::Neg(_),
+            expr,
+            ..
+        }) => number(expr).map(|value| -value),
+        _ => None,
+    };
+    value.filter(|value: &f64| value.is_finite())
+}
+
+fn labels(attr: &Attribute, states: &[&str]) -> syn::Result<Labels> {
+    let pairs = attr.parse_args_with(Punctuated::<MetaNameValue, Token![,]>::parse_terminated)?;
+    let mut labels = Labels::default();
+    for pair in pairs {
+        let Expr::Lit(ExprLit {
+            lit: Lit::Str(label),
+            ..
+        }) = &pair.value
+        else {
+            return Err(syn::Error::new_spanned(
+                &pair.value,
+                "Label must be a string literal",
+            ));
+        };
+        let state = pair.path.get_ident().map(ToString::to_string);
+        let slot = match state.as_deref() {
+            Some(state) if !states.contains(&state) => None,
+            Some("off") => Some(&mut labels.off),
+            Some("on") => Some(&mut labels.on),
+            Some("intermediate") => Some(&mut labels.intermediate),
+            Some("indeterminate") => Some(&mut labels.indeterminate),
+            _ => None,
+        };
+        let Some(slot) = slot else {
+            let message = format!("Unknown state. Expected one of: {}", states.join(", "));
+            return Err(syn::Error::new_spanned(&pair.path, message));
+        };
+        *slot = Some(label.value());
+    }
+    Ok(labels)
+}
+
+#[cfg(test)]
+mod test {
+    use micrortu_build_utils::{IEType, Range};
+    use syn::{parse_quote, Attribute};
+
+    use super::engineering;
+
+    #[test]
+    fn parses_attributes() {
+        let attrs: Vec<Attribute> = parse_quote! {
+            #[unit = "kW"]
+            #[range(-1.5, 100)]
+        };
+        let parsed = engineering(&attrs, IEType::TI13).unwrap();
+        assert_eq!(parsed.unit.as_deref(), Some("kW"));
+        assert_eq!(
+            parsed.range,
+            Some(Range {
+                min: -1.5,
+                max: 100.
+            })
+        );
+        assert!(engineering(&attrs, IEType::TI1).is_err());
+
+        let attrs: Vec<Attribute> = parse_quote! {
+            #[labels(off = "Closed", on = "Open")]
+        };
+        let labels = engineering(&attrs, IEType::TI1).unwrap().labels.unwrap();
+        assert_eq!(labels.off.as_deref(), Some("Closed"));
+        assert_eq!(labels.on.as_deref(), Some("Open"));
+        assert!(engineering(&attrs, IEType::TI13).is_err());
+
+        let attrs: Vec<Attribute> = parse_quote! { #[labels(intermediate = "Moving")] };
+        assert!(engineering(&attrs, IEType::TI3).is_ok());
+        assert!(engineering(&attrs, IEType::TI1).is_err());
+
+        let attrs: Vec<Attribute> = parse_quote! { #[range(10, 0)] };
+        assert!(engineering(&attrs, IEType::TI11).is_err());
+    }
+}
@@ -3,10 +3,14 @@
 use proc_macro::TokenStream;
 
 mod bindings;
+mod block;
 mod config;
+mod description;
+mod engineering;
 mod finalize;
 mod register_block;
 mod state;
+mod warning;
 
 /// Finalize the build process.
 /// That macro must be called at the end to embed metadata into the binary.
@@ -25,15 +29,78 @@ pub fn finalize(_: TokenStream) -> TokenStream {
 
 /// Register block.
 /// That macro should be called for each block to register it.
+///
+/// On native (non-wasm) targets it also emits a `BLOCK_{name}` constant of type
+/// `micrortu_sdk::RegisteredBlock`, which can be driven by `micrortu_sdk::testing`.
+///
+/// With `capacity = N` option the factory returns the block by value
+/// (`fn(&FactoryInput) -> Option<BlockType>`), and up to `N` instances are kept
+/// in SDK-managed storage. When it's full, the factory logs an error and
+/// returns `None`.
+///
+/// Optional lifecycle hooks are exported with `deinit = f` (`fn(&mut BlockType)`),
+/// `reset = f` (same signature as `step`) and `reconfigure = f`
+/// (`fn(&FactoryInput, &mut BlockType) -> StepResult`). Pooled blocks always
+/// export `deinit`, which frees the instance slot.
+///
+/// Doc comments or `#[description = "..."]` before the block type become the
+/// block description in metadata.
+///
+/// `version = "1.2.0"` sets the version of the block and `firmware = ">=0.16"`
+/// the firmware versions it works with, both are checked with `semver` and
+/// emitted into metadata.
+///
+/// `init`, `step`, `reset` and `reconfigure` may return either `StepResult` or
+/// `Result<(), E>` with `E: Into<micrortu_sdk::BlockError>`. Errors are logged
+/// and reported to the firmware by their code.
 /// # Example
 /// ```rust
 /// register_block!(BlockType, BlockName, factory, init, step);
+/// register_block!(BlockType, BlockName, factory, init, step, capacity = 4);
+/// register_block!(BlockType, BlockName, factory, init, step, capacity = 4, reset = reset);
+/// register_block!(BlockType, BlockName, factory, init, step, version = "1.2.0", firmware = ">=0.16");
+/// register_block!(
+///     /// Counts steps.
+///     BlockType, BlockName, factory, init, step
+/// );
 /// ```
 #[proc_macro]
 pub fn register_block(input: TokenStream) -> TokenStream {
     register_block::register_block(input)
 }
 
+/// Derive macro for `Block` trait.
+///
+/// Registers the block the same way `register_block!` does, generating
+/// `factory`, `init` and `step` that parse ports and params and call the trait
+/// methods.
+///
+/// `ports!`, `params!` and `Config` of the block must be declared before it.
+///
+/// ## Attributes
+///
+/// - `block_names`: the list of block names to register the type under.
+/// - `capacity`: how many instances factory can create (1 by default).
+/// - `hooks`: optional hooks to export, `reset` and/or `reconfigure`.
+///   `deinit` is always exported.
+/// - `description`: overrides doc comment used as block description in metadata.
+/// - `version`, `firmware`: block version and firmware requirement, same as
+///   in `register_block!`.
+///
+/// # Example
+/// ```ignore
+/// #[derive(Block)]
+/// #[block_names(counter)]
+/// pub struct Counter;
+/// ```
+#[proc_macro_derive(
+    Block,
+    attributes(block_names, capacity, hooks, description, version, firmware)
+)]
+pub fn derive_block(input: TokenStream) -> TokenStream {
+    block::derive_block(input)
+}
+
 /// Derive macro for `Config` trait.
 ///
 /// If block requires some configuration, it should be derived from `Config` trait.
@@ -45,7 +112,9 @@ pub fn register_block(input: TokenStream) -> TokenStream {
 ///
 /// - `block_names`: the list of blocks with that configuration.
 /// - `required`: whether user is required to provide this conf (true by default).
-#[proc_macro_derive(Config, attributes(block_names, required))]
+/// - `description`: on fields, overrides doc comment used as field description
+///   in metadata.
+#[proc_macro_derive(Config, attributes(block_names, required, description))]
 pub fn derive_config(input: TokenStream) -> TokenStream {
     config::derive_config(input)
 }
@@ -69,6 +138,48 @@ ports! {
 }
 ```
 
+Doc comments on bindings, or `#[description = "..."]`, become their description in metadata.
+
+`ANY` bindings accept elements of any type as `IEBuf`s. Accepted types can be
+restricted with `ANY(TI1 | TI3 | TI45)`, then elements of other types are
+rejected by the parser.
+
+Bindings can be viewed as plain values with `x: TI13 as f32 In 1 1`, which
+generates `x()` getter for inputs and `set_x(f32)` setter for outputs. Supported
+value types are `f32`, `i64`, `u64` and `bool`. Quality set by setters is chosen
+with `#[quality(propagate)]` (default), `#[quality(good)]` or `#[quality(keep)]`
+on the struct, see `micrortu_sdk::value`.
+
+With `#[track_changes]` on the struct, a `<Struct>Snapshot` is generated, to be
+stored in the block. It has `<name>_changed(&ports)` and `<name>_previous()`
+accessors for inputs, comparing both values and quality descriptors, see
+`micrortu_sdk::changes`.
+
+Elements of fixed-size bindings can be named, e.g. `u: TI13 In 3 3 [l1, l2, l3]`.
+Then `u_elements()` returns a `<Struct>U` view with a field per element, and
+`u_elements_mut()` is generated for outputs. Names are also passed to metadata.
+
+The struct also implements `micrortu_sdk::reflect::Reflect`, which exposes
+binding definitions and elements of parsed bindings without knowing the struct.
+
+`OUT` bindings are expected to be zeroed by the firmware. What the parser does
+otherwise is set with `#[not_zeroed(log)]` (default, zeroes them and logs once),
+`#[not_zeroed(zero)]` (zeroes them silently) or `#[not_zeroed(error)]` (returns
+`ParseError::NotZeroed`). Parse errors of bindings carry the binding index.
+
+Unbound optional bindings, passed with zero elements, parse as `None`.
+Optional `In` bindings can declare a default, used when they're unbound, e.g.
+`gain: TI13 In 1 1 ? = 1.0`. Then the field is not an `Option`.
+
+Engineering metadata for commissioning is set with `#[unit = "kW"]`,
+`#[range(0.0, 100.0)]` on numeric bindings and `#[labels(off = "Closed", on = "Open")]`
+on TI1, TI3, TI45 and TI46 bindings. Ranged measured value inputs also get a
+`<name>_checked()` accessor, see `micrortu_sdk::range`.
+
+Bindings must fit into `BINDINGS_BYTES_CAP` bytes of `Shared` when every binding
+has its maximum number of elements, otherwise it's a compile error. Unbounded
+bindings produce a warning with the number of elements that can fit.
+
 Resulting struct would have fields with types from those:
 
 `GetSingleOptional`
@@ -102,7 +213,10 @@ pub fn ports(input: TokenStream) -> TokenStream {
 }
 
 /**
-# Macros for generating parser of arguments block requires.
+# Macro for generating parser of params block requires.
+
+Same syntax and generated code as [`ports!`](macro@ports), which documents
+binding options.
 
 ## Example
 
@@ -119,33 +233,6 @@ params! {
     }
 }
 ```
-
-Resulting struct would have fields with types from those:
-
-`GetSingleOptional`
-
-`GetSingle`
-
-`GetMultipleOptional`
-
-`GetMultiple`
-
-`SetSingleOptional`
-
-`SetSingle`
-
-`SetMultipleOptional`
-
-`SetMultiple`
-
-`GetSetSingleOptional`
-
-`GetSetSingle`
-
-`GetSetMultipleOptional`
-
-`GetSetMultiple`
-
 */
 #[proc_macro]
 pub fn params(input: TokenStream) -> TokenStream {
@@ -1,122 +1,358 @@
-use micrortu_build_utils::{Block, Direction, Port};
+use micrortu_build_utils::{Block, Direction, Hook, Port};
 use proc_macro::TokenStream;
 use quote::{quote, ToTokens};
+use semver::{Version, VersionReq};
 use syn::{
     parse::{Parse, ParseStream},
-    parse_macro_input, Ident, Token,
+    parse_macro_input, Attribute, Ident, LitInt, LitStr, Token,
 };
 
-use crate::state::{get_block_conf, get_ports_params, intern_static_string, set_block, should_bail_on_duplicates};
+use crate::{
+    description::description,
+    state::{
+        get_block_conf, get_ports_params, intern_static_string, set_block,
+        should_bail_on_duplicates,
+    },
+};
+
+/// Functions implementing a block.
+/// Referenced relative to the module of the macro invocation.
+pub struct BlockFns {
+    pub factory: Ident,
+    pub init: Ident,
+    pub step: Ident,
+    pub deinit: Option<Ident>,
+    pub reset: Option<Ident>,
+    pub reconfigure: Option<Ident>,
+}
+
+/// Versions of a block emitted into metadata, validated at macro time.
+#[derive(Default)]
+pub struct BlockVersions {
+    /// Version of the block itself.
+    pub version: Option<String>,
+    /// Firmware versions the block works with.
+    pub firmware: Option<String>,
+}
+
+impl BlockVersions {
+    /// Sets `version` or `firmware` option from `lit`.
+    pub fn set(&mut self, key: &str, lit: &LitStr) -> syn::Result<()> {
+        match key {
+            "version" => {
+                let version = Version::parse(&lit.value()).map_err(|err| {
+                    syn::Error::new_spanned(lit, format!("Invalid block version: {err}"))
+                })?;
+                self.version = Some(version.to_string());
+            }
+            "firmware" => {
+                let requirement = VersionReq::parse(&lit.value()).map_err(|err| {
+                    syn::Error::new_spanned(lit, format!("Invalid firmware requirement: {err}"))
+                })?;
+                self.firmware = Some(requirement.to_string());
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    lit,
+                    "Unknown version option. Supported: version, firmware",
+                ))
+            }
+        }
+        Ok(())
+    }
+}
 
 struct RegisterBlockInput {
+    attrs: Vec<Attribute>,
     block_type: Ident,
     block_name: Ident,
-    factory_fn: Ident,
-    init_fn: Ident,
-    step_fn: Ident,
+    fns: BlockFns,
+    capacity: Option<usize>,
+    versions: BlockVersions,
 }
 
 impl Parse for RegisterBlockInput {
     fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
         let block_type = input.parse()?;
         input.parse::<Token![,]>()?;
         let block_name = input.parse()?;
         input.parse::<Token![,]>()?;
-        let factory_fn = input.parse()?;
+        let factory = input.parse()?;
         input.parse::<Token![,]>()?;
-        let init_fn = input.parse()?;
+        let init = input.parse()?;
         input.parse::<Token![,]>()?;
-        let step_fn = input.parse()?;
+        let step = input.parse()?;
+
+        let mut fns = BlockFns {
+            factory,
+            init,
+            step,
+            deinit: None,
+            reset: None,
+            reconfigure: None,
+        };
+        let mut capacity = None;
+        let mut versions = BlockVersions::default();
+        while input.parse::<Token![,]>().is_ok() && !input.is_empty() {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "capacity" => capacity = Some(parse_capacity(&input.parse()?)?),
+                "deinit" => fns.deinit = Some(input.parse()?),
+                "reset" => fns.reset = Some(input.parse()?),
+                "reconfigure" => fns.reconfigure = Some(input.parse()?),
+                key @ ("version" | "firmware") => versions.set(key, &input.parse()?)?,
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        key,
+                        "Unknown option. Supported: capacity, deinit, reset, reconfigure, \
+                         version, firmware",
+                    ))
+                }
+            }
+        }
 
         Ok(Self {
+            attrs,
             block_type,
             block_name,
-            factory_fn,
-            init_fn,
-            step_fn,
+            fns,
+            capacity,
+            versions,
         })
     }
 }
 
-#[allow(clippy::too_many_lines)]
 pub fn register_block(input: TokenStream) -> TokenStream {
     let RegisterBlockInput {
+        attrs,
         block_type,
         block_name,
-        factory_fn,
-        init_fn,
-        step_fn,
+        mut fns,
+        capacity,
+        versions,
     } = parse_macro_input!(input as RegisterBlockInput);
 
+    let mut output = quote! {};
+    if let Some(capacity) = capacity {
+        // Factory returns block by value, instances are kept in SDK-managed pool.
+        let factory = &fns.factory;
+        let deinit = fns.deinit.as_ref().map(|deinit| quote! { #deinit(block); });
+        let (storage, factory, deinit) = pooled(
+            &block_type,
+            &block_name,
+            capacity,
+            &quote! { #factory(input) },
+            deinit.as_ref(),
+        );
+        output = storage;
+        fns.factory = factory;
+        fns.deinit = Some(deinit);
+    }
+
+    let description = match description(&attrs) {
+        Ok(description) => description,
+        Err(err) => return err.into_compile_error().into(),
+    };
+    match expand(&block_type, &block_name, description, &versions, &fns) {
+        Ok(registered) => quote! { #output #registered }.into(),
+        Err(err) => err.into_compile_error().into(),
+    }
+}
+
+pub fn parse_capacity(lit: &LitInt) -> syn::Result<usize> {
+    match lit.base10_parse::<usize>()? {
+        0 => Err(syn::Error::new_spanned(
+            lit,
+            "Capacity must be greater than zero",
+        )),
+        capacity => Ok(capacity),
+    }
+}
+
+/// Emits a pool of `capacity` instances with factory and deinit functions for it.
+///
+/// Factory evaluates `create` (`Option<block_type>`, with `input: &FactoryInput`
+/// in scope) and moves the block into the pool. Deinit runs `deinit` (with
+/// `block: &mut block_type` in scope), then drops the block and frees its slot.
+pub fn pooled(
+    block_type: &Ident,
+    block_name: &Ident,
+    capacity: usize,
+    create: &proc_macro2::TokenStream,
+    deinit: Option<&proc_macro2::TokenStream>,
+) -> (proc_macro2::TokenStream, Ident, Ident) {
+    let pool_name = Ident::new(&format!("_POOL_{block_name}"), block_name.span());
+    let factory_fn = Ident::new(&format!("_factory_{block_name}"), block_name.span());
+    let deinit_fn = Ident::new(&format!("_deinit_{block_name}"), block_name.span());
+
+    let output = quote! {
+        #[allow(non_upper_case_globals)]
+        static #pool_name: ::micrortu_sdk::block::BlockPool<#block_type, #capacity> =
+            ::micrortu_sdk::block::BlockPool::new();
+
+        #[doc(hidden)]
+        fn #factory_fn(input: &::micrortu_sdk::FactoryInput) -> Option<&'static mut #block_type> {
+            ::micrortu_sdk::block::alloc(&#pool_name, #create)
+        }
+
+        #[doc(hidden)]
+        fn #deinit_fn(block: &mut #block_type) {
+            #deinit
+            ::micrortu_sdk::block::release(&#pool_name, block);
+        }
+    };
+    (output, factory_fn, deinit_fn)
+}
+
+/// Registers block `block_name` and emits its exports.
+#[allow(clippy::too_many_lines)]
+pub fn expand(
+    block_type: &Ident,
+    block_name: &Ident,
+    description: String,
+    versions: &BlockVersions,
+    fns: &BlockFns,
+) -> syn::Result<proc_macro2::TokenStream> {
     let block_name_str = block_name.to_string();
     if block_name_str.len() < 2 {
-        return syn::Error::new_spanned(
+        return Err(syn::Error::new_spanned(
             block_name,
             "Block name must be at least 2 characters long",
-        )
-        .to_compile_error()
-        .into();
+        ));
     }
     if block_name_str.len() > 32 {
-        return syn::Error::new_spanned(
+        return Err(syn::Error::new_spanned(
             block_name,
             "Block name must be at most 32 characters long",
-        )
-        .to_compile_error()
-        .into();
+        ));
     }
     if block_name_str.starts_with('_') {
-        return syn::Error::new_spanned(block_name, "Block name cannot start with underscore")
-            .to_compile_error()
-            .into();
+        return Err(syn::Error::new_spanned(
+            block_name,
+            "Block name cannot start with underscore",
+        ));
     }
     if block_name_str
         .chars()
         .any(|c| c.is_ascii_alphabetic() && !c.is_lowercase())
     {
-        return syn::Error::new_spanned(block_name, "Block name must be all lowercase")
-            .to_compile_error()
-            .into();
+        return Err(syn::Error::new_spanned(
+            block_name,
+            "Block name must be all lowercase",
+        ));
     }
 
     let module_name = Ident::new(&format!("_block_{block_name}"), block_name.span());
     let factory_name = Ident::new(&format!("factory_{block_name}"), block_name.span());
     let init_name = Ident::new(&format!("init_{block_name}"), block_name.span());
     let step_name = Ident::new(&format!("step_{block_name}"), block_name.span());
+    let deinit_name = Ident::new(&format!("deinit_{block_name}"), block_name.span());
+    let reset_name = Ident::new(&format!("reset_{block_name}"), block_name.span());
+    let reconfigure_name = Ident::new(&format!("reconfigure_{block_name}"), block_name.span());
     let ports_static_name = Ident::new(&format!("PORTS_{block_name}"), block_name.span());
     let params_static_name = Ident::new(&format!("PARAMS_{block_name}"), block_name.span());
+    let block_const_name = Ident::new(&format!("BLOCK_{block_name}"), block_name.span());
 
     let (ports, params) = get_ports_params(&block_name_str);
     let Some(ports) = ports else {
-        return syn::Error::new_spanned(block_name, "Missing ports for block")
-            .into_compile_error()
-            .into();
+        return Err(syn::Error::new_spanned(
+            block_name,
+            "Missing ports for block",
+        ));
     };
     let Some(params) = params else {
-        return syn::Error::new_spanned(block_name, "Missing params for block")
-            .into_compile_error()
-            .into();
+        return Err(syn::Error::new_spanned(
+            block_name,
+            "Missing params for block",
+        ));
     };
+    let hooks = [
+        (Hook::Deinit, &fns.deinit),
+        (Hook::Reset, &fns.reset),
+        (Hook::Reconfigure, &fns.reconfigure),
+    ]
+    .into_iter()
+    .filter_map(|(hook, f)| f.as_ref().map(|_| hook))
+    .collect();
     let block = Block {
         name: block_name_str.to_string(),
-        description: String::new(),
-        semver_requirement: None,
+        description,
+        version: versions.version.clone(),
+        semver_requirement: versions.firmware.clone(),
         ports: ports.clone(),
         params: params.clone(),
         block_conf: get_block_conf(&block_name_str),
+        hooks,
     };
     if set_block(&block_name_str, block).is_some() && should_bail_on_duplicates() {
-        return syn::Error::new_spanned(block_name, "Block with that name already exists")
-            .to_compile_error()
-            .into();
+        return Err(syn::Error::new_spanned(
+            block_name,
+            "Block with that name already exists",
+        ));
     }
     let ports = to_quote(ports);
     let params = to_quote(params);
 
+    let BlockFns {
+        factory: factory_fn,
+        init: init_fn,
+        step: step_fn,
+        deinit,
+        reset,
+        reconfigure,
+    } = fns;
+
+    let deinit_export = deinit.as_ref().map(|deinit_fn| {
+        quote! {
+            #[no_mangle]
+            extern "C" fn #deinit_name(block: &mut _BLOCK_TYPE) {
+                ::micrortu_sdk::panic::enter_block(&#block_name_str);
+                super::#deinit_fn(block)
+            }
+        }
+    });
+    let reset_export = reset.as_ref().map(|reset_fn| {
+        quote! {
+            #[no_mangle]
+            extern "C" fn #reset_name(shared: &mut Shared, block: &mut _BLOCK_TYPE) -> StepResult {
+                ::micrortu_sdk::panic::enter_block(&#block_name_str);
+                super::#reset_fn(shared, block).into_step_result()
+            }
+        }
+    });
+    let reconfigure_export = reconfigure.as_ref().map(|reconfigure_fn| {
+        quote! {
+            #[no_mangle]
+            extern "C" fn #reconfigure_name(input: &FactoryInput, block: &mut _BLOCK_TYPE) -> StepResult {
+                ::micrortu_sdk::panic::enter_block(&#block_name_str);
+                super::#reconfigure_fn(input, block).into_step_result()
+            }
+        }
+    });
+    // Host side converts the results the same way the exports do.
+    let deinit = match deinit {
+        Some(f) => quote! { Some(#f) },
+        None => quote! { None },
+    };
+    let reset = match reset {
+        Some(f) => quote! {
+            Some(|shared, block| ::micrortu_sdk::IntoStepResult::into_step_result(#f(shared, block)))
+        },
+        None => quote! { None },
+    };
+    let reconfigure = match reconfigure {
+        Some(f) => quote! {
+            Some(|input, block| ::micrortu_sdk::IntoStepResult::into_step_result(#f(input, block)))
+        },
+        None => quote! { None },
+    };
+
     let output = quote! {
         #[cfg(target_arch = "wasm32")]
         mod #module_name {
-            use ::micrortu_sdk::{Shared, StepResult, BindingDefinition, FactoryInput};
+            use ::micrortu_sdk::{Shared, StepResult, BindingDefinition, FactoryInput, IntoStepResult};
 
             use super::#factory_fn as factory_fn;
             use super::#init_fn as init_fn;
@@ -125,16 +361,22 @@ pub fn register_block(input: TokenStream) -> TokenStream {
 
             #[no_mangle]
             extern "C" fn #factory_name(shared: &FactoryInput) -> Option<&'static mut _BLOCK_TYPE> {
+                ::micrortu_sdk::panic::enter_block(&#block_name_str);
                 factory_fn(shared)
             }
             #[no_mangle]
             extern "C" fn #init_name(shared: &mut Shared, block: &mut _BLOCK_TYPE) -> StepResult {
-                init_fn(shared, block)
+                ::micrortu_sdk::panic::enter_block(&#block_name_str);
+                init_fn(shared, block).into_step_result()
             }
             #[no_mangle]
             extern "C" fn #step_name(shared: &mut Shared, block: &mut _BLOCK_TYPE) -> StepResult {
-                step_fn(shared, block)
+                ::micrortu_sdk::panic::enter_block(&#block_name_str);
+                step_fn(shared, block).into_step_result()
             }
+            #deinit_export
+            #reset_export
+            #reconfigure_export
             #[allow(non_upper_case_globals)]
             #[no_mangle]
             static #ports_static_name: &[BindingDefinition] = #ports;
@@ -142,8 +384,27 @@ pub fn register_block(input: TokenStream) -> TokenStream {
             #[no_mangle]
             static #params_static_name: &[BindingDefinition] = #params;
         }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        #[allow(non_upper_case_globals, dead_code)]
+        const #block_const_name: ::micrortu_sdk::RegisteredBlock<#block_type> =
+            ::micrortu_sdk::RegisteredBlock {
+                name: #block_name_str,
+                factory: #factory_fn,
+                init: |shared, block| {
+                    ::micrortu_sdk::IntoStepResult::into_step_result(#init_fn(shared, block))
+                },
+                step: |shared, block| {
+                    ::micrortu_sdk::IntoStepResult::into_step_result(#step_fn(shared, block))
+                },
+                deinit: #deinit,
+                reset: #reset,
+                reconfigure: #reconfigure,
+                ports: #ports,
+                params: #params,
+            };
     };
-    output.into()
+    Ok(output)
 }
 
 fn to_quote(ports: Vec<Port>) -> impl ToTokens {
@@ -174,9 +435,8 @@ fn to_quote(ports: Vec<Port>) -> impl ToTokens {
             }
         }
         #[cfg(not(target_arch = "wasm32"))] {
-            const NAME: &str = stringify!(#name);
             ::micrortu_sdk::NativeBindingDefinition::<'static> {
-                name: &NAME,
+                name: #name,
                 flags: #flags,
                 typ: #typ,
                 min_size: #min_size,
@@ -190,3 +450,25 @@ fn to_quote(ports: Vec<Port>) -> impl ToTokens {
 
     quote! { &[#(#ports),*] }
 }
+
+#[cfg(test)]
+mod test {
+    use syn::parse_quote;
+
+    use super::BlockVersions;
+
+    #[test]
+    fn versions() {
+        let mut versions = BlockVersions::default();
+        versions.set("version", &parse_quote!("1.2.0")).unwrap();
+        versions
+            .set("firmware", &parse_quote!(">=0.16,<2"))
+            .unwrap();
+        assert_eq!(versions.version.as_deref(), Some("1.2.0"));
+        assert_eq!(versions.firmware.as_deref(), Some(">=0.16, <2"));
+
+        assert!(versions.set("version", &parse_quote!("1.2")).is_err());
+        assert!(versions.set("firmware", &parse_quote!("latest")).is_err());
+        assert!(versions.set("revision", &parse_quote!("1.2.0")).is_err());
+    }
+}
@@ -0,0 +1,17 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote_spanned;
+
+/// Expands to an item that makes rustc warn with `note` at `name`.
+///
+/// Proc macros can't emit warnings on stable, so this references a
+/// `#[deprecated]` constant named `name`, and the deprecation note is shown.
+pub fn warning(name: &Ident, note: &str) -> TokenStream {
+    quote_spanned! {name.span()=>
+        const _: () = {
+            #[deprecated(note = #note)]
+            #[allow(non_upper_case_globals)]
+            const #name: () = ();
+            #name
+        };
+    }
+}
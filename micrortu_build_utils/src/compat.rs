@@ -0,0 +1,311 @@
+//! Checking blocks of a wasm bundle against the firmware they will run on.
+//!
+//! [`check`] compares [`WasmMetadata`] of a bundle with [`FirmwareDump`] of
+//! the target firmware and lists [`Conflict`]s, for tests and tooling.
+
+use std::fmt;
+
+use semver::{Version, VersionReq};
+
+use crate::{Block, FirmwareDump, IEType, Port, WasmMetadata};
+
+/// Incompatibility between a bundle and the firmware.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    /// Bundle block has the same name as a block of the firmware.
+    Shadowed { block: String },
+    /// Binding of a shadowing block differs from the firmware one in `field`,
+    /// either `"type"` or `"direction"`. Sizes and optionality aren't compared.
+    Binding {
+        block: String,
+        binding: String,
+        field: &'static str,
+    },
+    /// Binding type is missing in `supported_types` of the firmware.
+    UnsupportedType {
+        block: String,
+        binding: String,
+        typ: IEType,
+    },
+    /// Bundle requires firmware `required`, its `minimum_firmware_version`,
+    /// but the target is older.
+    FirmwareVersion {
+        required: (u8, u8, u8),
+        firmware: Version,
+    },
+    /// Bundle built with SDK `sdk` can't run on firmware hosting SDK
+    /// `firmware_sdk`: it is older or semver-incompatible.
+    SdkVersion {
+        sdk: (u8, u8, u8),
+        firmware_sdk: (u8, u8, u8),
+    },
+    /// Firmware doesn't match `semver_requirement` of the block, or it's invalid.
+    Requirement { block: String, requirement: String },
+}
+
+/// Conflicts of blocks in `metadata` with `firmware`.
+#[must_use]
+pub fn check(metadata: &WasmMetadata, firmware: &FirmwareDump) -> Vec<Conflict> {
+    let mut conflicts = vec![];
+    if !supports(&firmware.version, metadata.minimum_firmware_version) {
+        conflicts.push(Conflict::FirmwareVersion {
+            required: metadata.minimum_firmware_version,
+            firmware: firmware.version.clone(),
+        });
+    }
+    if let Some(firmware_sdk) = firmware.sdk_version {
+        if !sdk_compatible(metadata.sdk_version, firmware_sdk) {
+            conflicts.push(Conflict::SdkVersion {
+                sdk: metadata.sdk_version,
+                firmware_sdk,
+            });
+        }
+    }
+
+    for block in &metadata.blocks {
+        if let Some(requirement) = &block.semver_requirement {
+            let matches = VersionReq::parse(requirement)
+                .is_ok_and(|requirement| requirement.matches(&firmware.version));
+            if !matches {
+                conflicts.push(Conflict::Requirement {
+                    block: block.name.clone(),
+                    requirement: requirement.clone(),
+                });
+            }
+        }
+
+        let supported_types = firmware.supported_types.as_deref();
+        for port in block.ports.iter().chain(&block.params) {
+            let types = std::iter::once(port.typ).chain(port.allowed_types.iter().copied());
+            for typ in types {
+                if supported_types.is_some_and(|types| !types.contains(&typ)) {
+                    conflicts.push(Conflict::UnsupportedType {
+                        block: block.name.clone(),
+                        binding: port.name.clone(),
+                        typ,
+                    });
+                }
+            }
+        }
+
+        let Some(shadowed) = firmware.blocks.iter().find(|it| it.name == block.name) else {
+            continue;
+        };
+        conflicts.push(Conflict::Shadowed {
+            block: block.name.clone(),
+        });
+        compare(block, &block.ports, &shadowed.ports, &mut conflicts);
+        compare(block, &block.params, &shadowed.params, &mut conflicts);
+    }
+    conflicts
+}
+
+/// Whether `firmware` is at least `version`.
+fn supports(firmware: &Version, version: (u8, u8, u8)) -> bool {
+    let (major, minor, patch) = version;
+    (firmware.major, firmware.minor, firmware.patch) >= (major.into(), minor.into(), patch.into())
+}
+
+/// Whether firmware hosting SDK `firmware_sdk` runs bundles built with `sdk`.
+fn sdk_compatible(sdk: (u8, u8, u8), firmware_sdk: (u8, u8, u8)) -> bool {
+    let version = |(major, minor, patch): (u8, u8, u8)| {
+        Version::new(major.into(), minor.into(), patch.into())
+    };
+    VersionReq::parse(&format!("^{}", version(sdk)))
+        .is_ok_and(|requirement| requirement.matches(&version(firmware_sdk)))
+}
+
+fn compare(block: &Block, bundle: &[Port], firmware: &[Port], conflicts: &mut Vec<Conflict>) {
+    for port in bundle {
+        let Some(other) = firmware.iter().find(|it| it.name == port.name) else {
+            continue;
+        };
+        let fields = [
+            ("type", port.typ != other.typ),
+            ("direction", port.direction != other.direction),
+        ];
+        for (field, differs) in fields {
+            if differs {
+                conflicts.push(Conflict::Binding {
+                    block: block.name.clone(),
+                    binding: port.name.clone(),
+                    field,
+                });
+            }
+        }
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Shadowed { block } => {
+                write!(f, "block `{block}` shadows a block of the firmware")
+            }
+            Self::Binding {
+                block,
+                binding,
+                field,
+            } => write!(
+                f,
+                "binding `{binding}` of block `{block}` has different {field} in the firmware"
+            ),
+            Self::UnsupportedType {
+                block,
+                binding,
+                typ,
+            } => write!(
+                f,
+                "binding `{binding}` of block `{block}` has type {typ:?}, unsupported by the firmware"
+            ),
+            Self::FirmwareVersion { required, firmware } => write!(
+                f,
+                "bundle requires firmware {}.{}.{}, target is {firmware}",
+                required.0, required.1, required.2
+            ),
+            Self::SdkVersion { sdk, firmware_sdk } => write!(
+                f,
+                "bundle built with SDK {}.{}.{} can't run on firmware with SDK {}.{}.{}",
+                sdk.0, sdk.1, sdk.2, firmware_sdk.0, firmware_sdk.1, firmware_sdk.2
+            ),
+            Self::Requirement { block, requirement } => write!(
+                f,
+                "block `{block}` requires firmware `{requirement}`, which the target doesn't match"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU8;
+
+    use semver::Version;
+    use wasm_global_shared_data::{Direction, NativeBindingDefinition, IN, OUT, REQUIRED};
+
+    use super::{check, Conflict};
+    use crate::{Block, FirmwareDump, IEType, Port, WasmMetadata};
+
+    fn block(name: &str, ports: Vec<Port>) -> Block {
+        Block {
+            name: name.into(),
+            description: String::new(),
+            version: None,
+            semver_requirement: None,
+            ports,
+            params: vec![],
+            block_conf: None,
+            hooks: vec![],
+        }
+    }
+
+    fn port(typ: u8, direction: Direction) -> Port {
+        Port::try_from(NativeBindingDefinition {
+            name: "x",
+            flags: REQUIRED,
+            typ,
+            min_size: 1,
+            max_size: NonZeroU8::new(1),
+            direction,
+        })
+        .unwrap()
+    }
+
+    fn firmware(blocks: Vec<Block>) -> FirmwareDump {
+        FirmwareDump {
+            version: Version::new(0, 14, 2),
+            sdk_version: None,
+            supported_types: None,
+            blocks,
+        }
+    }
+
+    fn metadata(blocks: Vec<Block>) -> WasmMetadata {
+        WasmMetadata {
+            minimum_firmware_version: (0, 14, 0),
+            sdk_version: (0, 5, 21),
+            blocks,
+        }
+    }
+
+    #[test]
+    fn conflicts() {
+        let firmware = firmware(vec![block("pid", vec![port(13, IN)])]);
+        let mut metadata = metadata(vec![block("counter", vec![port(137, OUT)])]);
+        assert_eq!(check(&metadata, &firmware), []);
+
+        metadata.minimum_firmware_version = (0, 15, 0);
+        metadata.blocks[0].semver_requirement = Some(">=0.16".into());
+        metadata.blocks.push(block("pid", vec![port(200, OUT)]));
+        assert_eq!(
+            check(&metadata, &firmware),
+            [
+                Conflict::FirmwareVersion {
+                    required: (0, 15, 0),
+                    firmware: Version::new(0, 14, 2),
+                },
+                Conflict::Requirement {
+                    block: "counter".into(),
+                    requirement: ">=0.16".into(),
+                },
+                Conflict::Shadowed {
+                    block: "pid".into(),
+                },
+                Conflict::Binding {
+                    block: "pid".into(),
+                    binding: "x".into(),
+                    field: "type",
+                },
+                Conflict::Binding {
+                    block: "pid".into(),
+                    binding: "x".into(),
+                    field: "direction",
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported_type() {
+        let mut firmware = firmware(vec![]);
+        firmware.supported_types = Some(vec![IEType::TI13, IEType::TI137]);
+        let metadata = metadata(vec![
+            block("counter", vec![port(137, OUT)]),
+            block("setpoint", vec![port(200, OUT)]),
+        ]);
+        assert_eq!(
+            check(&metadata, &firmware),
+            [Conflict::UnsupportedType {
+                block: "setpoint".into(),
+                binding: "x".into(),
+                typ: IEType::TI200,
+            }]
+        );
+    }
+
+    #[test]
+    fn sdk_version() {
+        let mut firmware = firmware(vec![]);
+        let metadata = metadata(vec![]);
+        for (firmware_sdk, compatible) in [
+            ((0, 5, 21), true),
+            ((0, 5, 30), true),
+            ((0, 5, 10), false),
+            ((0, 6, 0), false),
+        ] {
+            firmware.sdk_version = Some(firmware_sdk);
+            let conflicts = check(&metadata, &firmware);
+            if compatible {
+                assert_eq!(conflicts, []);
+            } else {
+                assert_eq!(
+                    conflicts,
+                    [Conflict::SdkVersion {
+                        sdk: (0, 5, 21),
+                        firmware_sdk,
+                    }]
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,520 @@
+//! Changes between two versions of block metadata, classified by whether
+//! existing projects keep working with the new version.
+//!
+//! [`diff_metadata`] and [`diff_block`] list [`Change`]s, and
+//! [`version_allows`] checks that the block version was bumped accordingly.
+
+use std::fmt;
+
+use semver::{Version, VersionReq};
+
+use crate::{AllowedType, Block, BlockConf, Direction, IEType, Port, WasmMetadata};
+
+/// Whether existing projects keep working after a change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Compatible,
+    Breaking,
+}
+
+/// Part of a block that changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    Block,
+    Port(String),
+    Param(String),
+    Config,
+    ConfigField(String),
+}
+
+/// What changed, with old and new values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Type {
+        old: IEType,
+        new: IEType,
+    },
+    /// Types accepted by an `any` binding.
+    AllowedTypes,
+    Direction {
+        old: Direction,
+        new: Direction,
+    },
+    Min {
+        old: u8,
+        new: u8,
+    },
+    /// Maximum number of elements, `None` if unbounded.
+    Max {
+        old: Option<u8>,
+        new: Option<u8>,
+    },
+    Required {
+        old: bool,
+        new: bool,
+    },
+    FieldType {
+        old: AllowedType,
+        new: AllowedType,
+    },
+    /// Config field moved, which changes the layout of config.
+    FieldPosition {
+        old: usize,
+        new: usize,
+    },
+}
+
+/// Change of `item` of `block`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub block: String,
+    pub item: Item,
+    pub kind: ChangeKind,
+    pub severity: Severity,
+}
+
+/// Changes of blocks between `old` and `new` metadata, matched by name.
+#[must_use]
+pub fn diff_metadata(old: &WasmMetadata, new: &WasmMetadata) -> Vec<Change> {
+    let mut changes = vec![];
+    for block in &old.blocks {
+        match new.blocks.iter().find(|it| it.name == block.name) {
+            Some(new) => changes.extend(diff_block(block, new)),
+            None => changes.push(Change {
+                block: block.name.clone(),
+                item: Item::Block,
+                kind: ChangeKind::Removed,
+                severity: Severity::Breaking,
+            }),
+        }
+    }
+    for block in &new.blocks {
+        if !old.blocks.iter().any(|it| it.name == block.name) {
+            changes.push(Change {
+                block: block.name.clone(),
+                item: Item::Block,
+                kind: ChangeKind::Added,
+                severity: Severity::Compatible,
+            });
+        }
+    }
+    changes
+}
+
+/// Changes of bindings and config between `old` and `new` versions of a block.
+/// Descriptions and other display-only metadata are not compared.
+#[must_use]
+pub fn diff_block(old: &Block, new: &Block) -> Vec<Change> {
+    let mut changes = vec![];
+    let mut push = |item: Item, kind: ChangeKind, severity: Severity| {
+        changes.push(Change {
+            block: new.name.clone(),
+            item,
+            kind,
+            severity,
+        });
+    };
+    diff_bindings(&old.ports, &new.ports, &Item::Port, &mut push);
+    diff_bindings(&old.params, &new.params, &Item::Param, &mut push);
+    diff_config(old.block_conf.as_ref(), new.block_conf.as_ref(), &mut push);
+    changes
+}
+
+/// Whether `new` version of a block is allowed after `changes` from `old`:
+/// breaking changes need a semver-incompatible bump.
+#[must_use]
+pub fn version_allows(old: &Version, new: &Version, changes: &[Change]) -> bool {
+    let breaking = changes
+        .iter()
+        .any(|change| change.severity == Severity::Breaking);
+    let compatible = VersionReq::parse(&format!("^{old}")).is_ok_and(|req| req.matches(new));
+    new >= old && !(breaking && compatible)
+}
+
+fn breaking(is_breaking: bool) -> Severity {
+    if is_breaking {
+        Severity::Breaking
+    } else {
+        Severity::Compatible
+    }
+}
+
+fn diff_bindings(
+    old: &[Port],
+    new: &[Port],
+    item: &dyn Fn(String) -> Item,
+    push: &mut impl FnMut(Item, ChangeKind, Severity),
+) {
+    use Severity::Breaking;
+
+    for port in old {
+        let Some(other) = new.iter().find(|it| it.name == port.name) else {
+            push(item(port.name.clone()), ChangeKind::Removed, Breaking);
+            continue;
+        };
+        let mut push = |kind, severity| push(item(port.name.clone()), kind, severity);
+        if port.typ != other.typ {
+            // Existing projects bind `port.typ`; empty list accepts all types.
+            let accepted = other.typ == IEType::Any
+                && (other.allowed_types.is_empty() || other.allowed_types.contains(&port.typ));
+            let kind = ChangeKind::Type {
+                old: port.typ,
+                new: other.typ,
+            };
+            push(kind, breaking(!accepted));
+        } else if port.allowed_types != other.allowed_types {
+            // Empty list accepts all types.
+            let narrowed = !other.allowed_types.is_empty()
+                && (port.allowed_types.is_empty()
+                    || port
+                        .allowed_types
+                        .iter()
+                        .any(|typ| !other.allowed_types.contains(typ)));
+            push(ChangeKind::AllowedTypes, breaking(narrowed));
+        }
+        if port.direction != other.direction {
+            let kind = ChangeKind::Direction {
+                old: port.direction,
+                new: other.direction,
+            };
+            push(kind, Breaking);
+        }
+        if port.min != other.min {
+            let kind = ChangeKind::Min {
+                old: port.min.get(),
+                new: other.min.get(),
+            };
+            push(kind, breaking(other.min > port.min));
+        }
+        if port.max != other.max {
+            let lowered = match (port.max, other.max) {
+                (Some(old), Some(new)) => new < old,
+                (old, new) => old.is_none() && new.is_some(),
+            };
+            let kind = ChangeKind::Max {
+                old: port.max.map(|max| max.get()),
+                new: other.max.map(|max| max.get()),
+            };
+            push(kind, breaking(lowered));
+        }
+        if port.required != other.required {
+            let kind = ChangeKind::Required {
+                old: port.required,
+                new: other.required,
+            };
+            push(kind, breaking(other.required));
+        }
+    }
+    for port in new {
+        if !old.iter().any(|it| it.name == port.name) {
+            let severity = breaking(port.required);
+            push(item(port.name.clone()), ChangeKind::Added, severity);
+        }
+    }
+}
+
+/// Any change of config fields changes its layout, so only `required` can
+/// be relaxed compatibly. Appended fields are breaking too: config of existing
+/// projects is shorter than the new one and fails to read.
+fn diff_config(
+    old: Option<&BlockConf>,
+    new: Option<&BlockConf>,
+    push: &mut impl FnMut(Item, ChangeKind, Severity),
+) {
+    use Severity::Breaking;
+
+    let (old, new) = match (old, new) {
+        (None, None) => return,
+        (Some(_), None) => return push(Item::Config, ChangeKind::Removed, Breaking),
+        (None, Some(new)) => {
+            let severity = breaking(new.required);
+            return push(Item::Config, ChangeKind::Added, severity);
+        }
+        (Some(old), Some(new)) => (old, new),
+    };
+    if old.required != new.required {
+        let kind = ChangeKind::Required {
+            old: old.required,
+            new: new.required,
+        };
+        push(Item::Config, kind, breaking(new.required));
+    }
+    for (position, (name, typ)) in old.fields.iter().enumerate() {
+        let item = || Item::ConfigField(name.clone());
+        let Some(new_position) = new.fields.iter().position(|(it, _)| it == name) else {
+            push(item(), ChangeKind::Removed, Breaking);
+            continue;
+        };
+        let new_typ = new.fields[new_position].1;
+        if *typ != new_typ {
+            let kind = ChangeKind::FieldType {
+                old: *typ,
+                new: new_typ,
+            };
+            push(item(), kind, Breaking);
+        }
+        if position != new_position {
+            let kind = ChangeKind::FieldPosition {
+                old: position,
+                new: new_position,
+            };
+            push(item(), kind, Breaking);
+        }
+    }
+    for (name, _) in &new.fields {
+        if !old.fields.iter().any(|(it, _)| it == name) {
+            push(Item::ConfigField(name.clone()), ChangeKind::Added, Breaking);
+        }
+    }
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Block => write!(f, "block"),
+            Self::Port(name) => write!(f, "port `{name}`"),
+            Self::Param(name) => write!(f, "param `{name}`"),
+            Self::Config => write!(f, "config"),
+            Self::ConfigField(name) => write!(f, "config field `{name}`"),
+        }
+    }
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unbounded = |max: &Option<u8>| max.map_or("unbounded".into(), |max| max.to_string());
+        match self {
+            Self::Added => write!(f, "added"),
+            Self::Removed => write!(f, "removed"),
+            Self::Type { old, new } => write!(f, "type changed from {old:?} to {new:?}"),
+            Self::AllowedTypes => write!(f, "allowed types changed"),
+            Self::Direction { old, new } => {
+                write!(f, "direction changed from {old:?} to {new:?}")
+            }
+            Self::Min { old, new } => write!(f, "min changed from {old} to {new}"),
+            Self::Max { old, new } => {
+                write!(
+                    f,
+                    "max changed from {} to {}",
+                    unbounded(old),
+                    unbounded(new)
+                )
+            }
+            Self::Required { old, new } => write!(f, "required changed from {old} to {new}"),
+            Self::FieldType { old, new } => write!(f, "type changed from {old:?} to {new:?}"),
+            Self::FieldPosition { old, new } => {
+                write!(f, "moved from position {old} to {new}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Compatible => "compatible",
+            Severity::Breaking => "breaking",
+        };
+        write!(
+            f,
+            "{severity}: {} of block `{}` {}",
+            self.item, self.block, self.kind
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU8;
+
+    use semver::Version;
+    use wasm_global_shared_data::{NativeBindingDefinition, IN, IN_OUT, REQUIRED};
+
+    use super::{diff_block, version_allows, Change, ChangeKind, Item, Severity};
+    use crate::{AllowedType, Block, BlockConf, Direction, IEType, Port};
+
+    fn port(
+        name: &'static str,
+        min_size: u8,
+        max_size: u8,
+        direction: wasm_global_shared_data::Direction,
+    ) -> Port {
+        Port::try_from(NativeBindingDefinition {
+            name,
+            flags: REQUIRED,
+            typ: 13,
+            min_size,
+            max_size: NonZeroU8::new(max_size),
+            direction,
+        })
+        .unwrap()
+    }
+
+    fn block(ports: Vec<Port>, fields: Vec<(String, AllowedType)>) -> Block {
+        Block {
+            name: "b".into(),
+            description: String::new(),
+            version: None,
+            semver_requirement: None,
+            ports,
+            params: vec![],
+            block_conf: Some(BlockConf {
+                required: true,
+                fields,
+                descriptions: Default::default(),
+            }),
+            hooks: vec![],
+        }
+    }
+
+    fn fields() -> Vec<(String, AllowedType)> {
+        vec![
+            ("k".into(), AllowedType::F32),
+            ("n".into(), AllowedType::U8),
+        ]
+    }
+
+    fn summary(changes: &[Change]) -> Vec<(&Item, &ChangeKind, Severity)> {
+        changes
+            .iter()
+            .map(|change| (&change.item, &change.kind, change.severity))
+            .collect()
+    }
+
+    #[test]
+    fn unchanged() {
+        let old = block(vec![port("x", 1, 1, IN)], fields());
+        assert_eq!(diff_block(&old, &old.clone()), []);
+    }
+
+    #[test]
+    fn binding_changes() {
+        let old = block(
+            vec![
+                port("x", 1, 1, IN),
+                port("y", 2, 4, IN),
+                port("z", 1, 1, IN),
+            ],
+            fields(),
+        );
+        let mut optional = port("w", 1, 1, IN);
+        optional.required = false;
+        let new = block(
+            vec![port("x", 1, 1, IN_OUT), port("y", 1, 8, IN), optional],
+            fields(),
+        );
+        assert_eq!(
+            summary(&diff_block(&old, &new)),
+            [
+                (
+                    &Item::Port("x".into()),
+                    &ChangeKind::Direction {
+                        old: Direction::In,
+                        new: Direction::InOut
+                    },
+                    Severity::Breaking
+                ),
+                (
+                    &Item::Port("y".into()),
+                    &ChangeKind::Min { old: 2, new: 1 },
+                    Severity::Compatible
+                ),
+                (
+                    &Item::Port("y".into()),
+                    &ChangeKind::Max {
+                        old: Some(4),
+                        new: Some(8)
+                    },
+                    Severity::Compatible
+                ),
+                (
+                    &Item::Port("z".into()),
+                    &ChangeKind::Removed,
+                    Severity::Breaking
+                ),
+                (
+                    &Item::Port("w".into()),
+                    &ChangeKind::Added,
+                    Severity::Compatible
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn any_type() {
+        let old = block(vec![port("x", 1, 1, IN)], fields());
+        let mut new = old.clone();
+        new.ports[0].typ = IEType::Any;
+        for (allowed_types, severity) in [
+            (vec![], Severity::Compatible),
+            (vec![IEType::TI13, IEType::TI137], Severity::Compatible),
+            (vec![IEType::TI137], Severity::Breaking),
+        ] {
+            new.ports[0].allowed_types = allowed_types;
+            let changes = diff_block(&old, &new);
+            assert_eq!(changes.len(), 1);
+            assert_eq!(changes[0].severity, severity);
+        }
+    }
+
+    #[test]
+    fn version_bump() {
+        let old = block(vec![port("x", 1, 1, IN)], fields());
+        let new = block(vec![], fields());
+        let changes = diff_block(&old, &new);
+        assert!(!version_allows(
+            &Version::new(1, 2, 0),
+            &Version::new(1, 3, 0),
+            &changes
+        ));
+        assert!(version_allows(
+            &Version::new(1, 2, 0),
+            &Version::new(2, 0, 0),
+            &changes
+        ));
+        assert!(version_allows(
+            &Version::new(1, 2, 0),
+            &Version::new(1, 3, 0),
+            &[]
+        ));
+    }
+
+    #[test]
+    fn config_field_changes() {
+        let old = block(vec![], fields());
+        let new = block(
+            vec![],
+            vec![
+                ("n".into(), AllowedType::U8),
+                ("k".into(), AllowedType::F64),
+            ],
+        );
+        let changes = diff_block(&old, &new);
+        assert_eq!(changes.len(), 3);
+        assert!(changes
+            .iter()
+            .all(|change| change.severity == Severity::Breaking));
+        assert_eq!(
+            changes[0].to_string(),
+            "breaking: config field `k` of block `b` type changed from F32 to F64"
+        );
+    }
+
+    #[test]
+    fn appended_config_field() {
+        let old = block(vec![], fields());
+        let mut fields = fields();
+        fields.push(("m".into(), AllowedType::U16));
+        assert_eq!(
+            summary(&diff_block(&old, &block(vec![], fields))),
+            [(
+                &Item::ConfigField("m".into()),
+                &ChangeKind::Added,
+                Severity::Breaking
+            )]
+        );
+    }
+}
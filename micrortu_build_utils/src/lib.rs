@@ -1,10 +1,20 @@
-use std::num::NonZeroU8;
+use std::{collections::BTreeMap, num::NonZeroU8};
 
+use ie_base::{IEBuf, IeType, SmallIE};
 use schemars::JsonSchema;
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use validator::Validate;
-use wasm_global_shared_data::{NativeBindingDefinition, REQUIRED};
+use wasm_global_shared_data::{NativeBindingDefinition, ANY, REQUIRED};
+
+pub use wasm_global_shared_data::BINDINGS_BYTES_CAP;
+
+#[cfg(feature = "artifact")]
+pub mod compat;
+#[cfg(feature = "artifact")]
+pub mod diff;
+#[cfg(feature = "artifact")]
+pub mod wasm;
 
 #[derive(Serialize, JsonSchema, Deserialize, Debug)]
 pub struct WasmMetadata {
@@ -13,9 +23,17 @@ pub struct WasmMetadata {
     pub blocks: Vec<Block>,
 }
 
-#[derive(Serialize, JsonSchema)]
+#[derive(Serialize, JsonSchema, Deserialize, Debug)]
 pub struct FirmwareDump {
     pub version: Version,
+    /// SDK version the firmware hosts wasm blocks with.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sdk_version: Option<(u8, u8, u8)>,
+    /// Binding types the firmware supports, `None` if the dump doesn't list them.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supported_types: Option<Vec<IEType>>,
     pub blocks: Vec<Block>,
 }
 
@@ -24,6 +42,11 @@ pub struct Block {
     #[validate(length(min = 1, max = 32))]
     pub name: String,
     pub description: String,
+    /// Version of the block itself, bumped when the block changes.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Firmware versions the block works with, as a semver requirement.
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub semver_requirement: Option<String>,
@@ -32,6 +55,22 @@ pub struct Block {
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub block_conf: Option<BlockConf>,
+    /// Optional lifecycle hooks exported by the block.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<Hook>,
+}
+
+/// Optional lifecycle export of a block, in addition to `factory`, `init` and `step`.
+#[derive(Serialize, JsonSchema, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Hook {
+    /// `deinit_{name}`, called when the instance is released.
+    Deinit,
+    /// `reset_{name}`, called on process restart without reloading the block.
+    Reset,
+    /// `reconfigure_{name}`, called to apply new `FactoryInput::config` live.
+    Reconfigure,
 }
 
 #[derive(Serialize, JsonSchema, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
@@ -46,6 +85,8 @@ pub enum Direction {
 #[repr(u8)]
 #[serde(rename_all = "lowercase")]
 pub enum IEType {
+    /// Any of the types below, see `allowed_types` of `Port`.
+    Any = ANY,
     TI1 = 1,
     TI3 = 3,
     TI11 = 11,
@@ -78,12 +119,91 @@ pub struct Port {
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max: Option<NonZeroU8>,
+    /// Value used by the block when optional binding is unbound.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<DefaultValue>,
+    /// Physical unit of the value, like `kW`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    /// Valid engineering range of the value.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<Range>,
+    /// Names of the states of TI1, TI3, TI45 and TI46 bindings.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Labels>,
+    /// Types accepted by `any` bindings. Empty if all types are accepted.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub allowed_types: Vec<IEType>,
+    /// Names of elements of fixed-size bindings, one per binding slot.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub element_names: Vec<String>,
+}
+
+/// Inclusive range of valid values.
+#[derive(Serialize, JsonSchema, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Range {
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Display names of single and double point states.
+///
+/// `intermediate` and `indeterminate` apply only to double points.
+#[derive(Serialize, JsonSchema, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Labels {
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub off: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub intermediate: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub indeterminate: Option<String>,
+}
+
+/// Default value of an optional port or param.
+#[derive(Serialize, JsonSchema, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum DefaultValue {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+}
+
+impl Port {
+    /// Bytes taken by payload of `elements` elements, including worst-case padding.
+    #[must_use]
+    pub fn payload_size(&self, elements: usize) -> usize {
+        self.typ.align() - 1 + self.typ.size() * elements
+    }
+}
+
+/// Worst-case size of encoded `bindings`, with unbounded ones counted at their minimum.
+#[must_use]
+pub fn bindings_size(bindings: &[Port]) -> usize {
+    let payloads = bindings.iter().map(|port| {
+        let elements = port.max.unwrap_or(port.min).get();
+        port.payload_size(elements.into())
+    });
+    bindings.len() * 2 + payloads.sum::<usize>()
 }
 
 impl TryFrom<u8> for IEType {
     type Error = ConvertError;
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
+            ANY => Ok(Self::Any),
             1 => Ok(Self::TI1),
             3 => Ok(Self::TI3),
             11 => Ok(Self::TI11),
@@ -106,10 +226,30 @@ impl TryFrom<u8> for IEType {
     }
 }
 
+impl IEType {
+    /// Size of a single element in encoded bindings.
+    #[must_use]
+    pub fn size(self) -> usize {
+        self.ie_type()
+            .map_or(size_of::<IEBuf>(), SmallIE::size_for_type)
+    }
+
+    /// Alignment of elements in encoded bindings.
+    #[must_use]
+    pub fn align(self) -> usize {
+        self.ie_type()
+            .map_or(align_of::<IEBuf>(), SmallIE::align_for_type)
+    }
+
+    fn ie_type(self) -> Option<IeType> {
+        IeType::new(self as u8).ok()
+    }
+}
+
 #[derive(Debug)]
 pub struct ConvertError;
 
-impl TryFrom<NativeBindingDefinition<'static>> for Port {
+impl TryFrom<NativeBindingDefinition<'_>> for Port {
     type Error = ConvertError;
     fn try_from(value: NativeBindingDefinition) -> Result<Self, Self::Error> {
         Ok(Self {
@@ -125,6 +265,12 @@ impl TryFrom<NativeBindingDefinition<'static>> for Port {
             required: value.flags & REQUIRED != 0,
             min: value.min_size.try_into().map_err(|_| ConvertError)?,
             max: value.max_size,
+            default: None,
+            unit: None,
+            range: None,
+            labels: None,
+            allowed_types: Vec::new(),
+            element_names: Vec::new(),
         })
     }
 }
@@ -153,4 +299,8 @@ pub struct BlockConf {
     #[serde(default = "default_required")]
     pub required: bool,
     pub fields: Vec<(String, AllowedType)>,
+    /// Descriptions of the fields, by field name.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub descriptions: BTreeMap<String, String>,
 }
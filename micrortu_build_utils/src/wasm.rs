@@ -0,0 +1,532 @@
+//! Reading built `.wasm` files back, for checking release artifacts.
+//!
+//! `finalize!` writes [`WasmMetadata`] as JSON into the `metadata` custom
+//! section, while `register_block!` exports `PORTS_{block}` and
+//! `PARAMS_{block}` tables of `BindingDefinition`s, with names stored in the
+//! exported `COLLECTED_STRINGS`. [`WasmArtifact::parse`] reads both, and
+//! [`WasmArtifact::mismatches`] checks that they agree.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use wasm_global_shared_data::{BindingDefinition, NativeBindingDefinition};
+use wasmparser::{ConstExpr, DataKind, ExternalKind, Operator, Parser, Payload, TypeRef};
+use zerocopy::FromBytes;
+
+use crate::{Hook, Port, WasmMetadata};
+
+/// Name of the custom section written by `finalize!`.
+pub const METADATA_SECTION: &str = "metadata";
+
+/// Metadata and exports of a `.wasm` file built with the SDK.
+#[derive(Debug)]
+pub struct WasmArtifact {
+    pub metadata: WasmMetadata,
+    /// Bindings from `PORTS_{block}` exports, by block name.
+    pub ports: BTreeMap<String, Vec<Port>>,
+    /// Bindings from `PARAMS_{block}` exports, by block name.
+    pub params: BTreeMap<String, Vec<Port>>,
+    /// Names of exported functions.
+    pub functions: BTreeSet<String>,
+}
+
+#[derive(Debug)]
+pub enum WasmError {
+    /// The file is not a valid wasm module.
+    Parse(wasmparser::BinaryReaderError),
+    /// There is no `metadata` custom section.
+    MissingMetadata,
+    /// `metadata` section is not a valid `WasmMetadata`.
+    Metadata(serde_json::Error),
+    /// `COLLECTED_STRINGS` is not exported.
+    MissingStrings,
+    /// Exported global doesn't point into data segments.
+    Unresolved(String),
+    /// Binding table has invalid name or definition.
+    InvalidTable(String),
+}
+
+/// Disagreement between metadata and exports of a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// Block from metadata doesn't have that export.
+    MissingExport { block: String, export: String },
+    /// Block is exported, but missing in metadata.
+    UnknownBlock { block: String },
+    /// Binding tables have different number of bindings.
+    Count {
+        export: String,
+        metadata: usize,
+        exported: usize,
+    },
+    /// Binding at `index` differs in `field`.
+    Binding {
+        export: String,
+        index: usize,
+        field: &'static str,
+    },
+}
+
+impl WasmArtifact {
+    /// Parses `wasm` and resolves binding tables of all exported blocks.
+    pub fn parse(wasm: &[u8]) -> Result<Self, WasmError> {
+        let module = Module::parse(wasm)?;
+        let metadata = module.metadata.ok_or(WasmError::MissingMetadata)?;
+        let metadata = serde_json::from_slice(metadata).map_err(WasmError::Metadata)?;
+        let strings = module
+            .globals
+            .get("COLLECTED_STRINGS")
+            .copied()
+            .flatten()
+            .and_then(|address| module.memory(address, 0))
+            .ok_or(WasmError::MissingStrings)?;
+
+        let mut ports = BTreeMap::new();
+        let mut params = BTreeMap::new();
+        for (export, address) in &module.globals {
+            let (bindings, block) = if let Some(block) = export.strip_prefix("PORTS_") {
+                (&mut ports, block)
+            } else if let Some(block) = export.strip_prefix("PARAMS_") {
+                (&mut params, block)
+            } else {
+                continue;
+            };
+            let table = address
+                .and_then(|address| module.table(address))
+                .ok_or_else(|| WasmError::Unresolved(export.clone()))?;
+            let table = table
+                .iter()
+                .map(|&def| resolve(def, strings))
+                .collect::<Option<_>>()
+                .ok_or_else(|| WasmError::InvalidTable(export.clone()))?;
+            bindings.insert(block.to_string(), table);
+        }
+
+        Ok(Self {
+            metadata,
+            ports,
+            params,
+            functions: module.functions,
+        })
+    }
+
+    /// Differences between metadata and exports. Empty if they agree.
+    #[must_use]
+    pub fn mismatches(&self) -> Vec<Mismatch> {
+        let mut mismatches = vec![];
+        for block in &self.metadata.blocks {
+            let missing = |export: &String| Mismatch::MissingExport {
+                block: block.name.clone(),
+                export: export.clone(),
+            };
+            let hooks = block.hooks.iter().map(|hook| match hook {
+                Hook::Deinit => "deinit_",
+                Hook::Reset => "reset_",
+                Hook::Reconfigure => "reconfigure_",
+            });
+            for prefix in ["factory_", "init_", "step_"].into_iter().chain(hooks) {
+                let export = format!("{prefix}{}", block.name);
+                if !self.functions.contains(&export) {
+                    mismatches.push(missing(&export));
+                }
+            }
+            let tables = [
+                ("PORTS_", &block.ports, &self.ports),
+                ("PARAMS_", &block.params, &self.params),
+            ];
+            for (prefix, expected, tables) in tables {
+                let export = format!("{prefix}{}", block.name);
+                match tables.get(&block.name) {
+                    Some(exported) => compare(&export, expected, exported, &mut mismatches),
+                    None => mismatches.push(missing(&export)),
+                }
+            }
+        }
+
+        // Only a full set of `register_block!` exports is a block, other
+        // exports may share prefixes, like `init_heap`.
+        let blocks = self.ports.keys().filter(|block| {
+            let functions = ["factory_", "init_", "step_"]
+                .iter()
+                .all(|prefix| self.functions.contains(&format!("{prefix}{block}")));
+            functions && self.params.contains_key(*block)
+        });
+        for block in blocks {
+            if !self.metadata.blocks.iter().any(|it| it.name == *block) {
+                mismatches.push(Mismatch::UnknownBlock {
+                    block: block.clone(),
+                });
+            }
+        }
+        mismatches
+    }
+}
+
+/// Compares bindings from metadata with ones from exported table.
+fn compare(export: &str, expected: &[Port], exported: &[Port], mismatches: &mut Vec<Mismatch>) {
+    if expected.len() != exported.len() {
+        mismatches.push(Mismatch::Count {
+            export: export.to_string(),
+            metadata: expected.len(),
+            exported: exported.len(),
+        });
+    }
+    for (index, (expected, exported)) in expected.iter().zip(exported).enumerate() {
+        let fields = [
+            ("name", expected.name == exported.name),
+            ("type", expected.typ == exported.typ),
+            ("direction", expected.direction == exported.direction),
+            ("required", expected.required == exported.required),
+            ("min", expected.min == exported.min),
+            ("max", expected.max == exported.max),
+        ];
+        for (field, _) in fields.into_iter().filter(|(_, equal)| !equal) {
+            mismatches.push(Mismatch::Binding {
+                export: export.to_string(),
+                index,
+                field,
+            });
+        }
+    }
+}
+
+/// Converts `def` to a `Port`, resolving its name in `strings`.
+fn resolve(def: BindingDefinition, strings: &[u8]) -> Option<Port> {
+    NativeBindingDefinition {
+        name: def.name(strings)?,
+        flags: def.flags,
+        typ: def.typ,
+        min_size: def.min_size,
+        max_size: def.max_size,
+        direction: def.direction,
+    }
+    .try_into()
+    .ok()
+}
+
+/// Parts of a wasm module needed to read metadata and binding tables.
+#[derive(Default)]
+struct Module<'a> {
+    metadata: Option<&'a [u8]>,
+    /// Values of exported `i32` globals, i.e. addresses of exported statics.
+    /// `None` for globals not initialized with a constant, like imported ones.
+    globals: BTreeMap<String, Option<u32>>,
+    functions: BTreeSet<String>,
+    /// Active data segments with their addresses.
+    segments: Vec<(u32, &'a [u8])>,
+}
+
+impl<'a> Module<'a> {
+    fn parse(wasm: &'a [u8]) -> Result<Self, WasmError> {
+        let mut module = Self::default();
+        let mut globals = vec![];
+        let mut exports = vec![];
+        for payload in Parser::new(0).parse_all(wasm) {
+            match payload.map_err(WasmError::Parse)? {
+                Payload::ImportSection(reader) => {
+                    for import in reader.into_imports() {
+                        let import = import.map_err(WasmError::Parse)?;
+                        if matches!(import.ty, TypeRef::Global(_)) {
+                            globals.push(None);
+                        }
+                    }
+                }
+                Payload::GlobalSection(reader) => {
+                    for global in reader {
+                        let global = global.map_err(WasmError::Parse)?;
+                        globals.push(const_i32(&global.init_expr));
+                    }
+                }
+                Payload::ExportSection(reader) => {
+                    for export in reader {
+                        exports.push(export.map_err(WasmError::Parse)?);
+                    }
+                }
+                Payload::DataSection(reader) => {
+                    for data in reader {
+                        let data = data.map_err(WasmError::Parse)?;
+                        if let DataKind::Active { offset_expr, .. } = data.kind {
+                            if let Some(address) = const_i32(&offset_expr) {
+                                module.segments.push((address, data.data));
+                            }
+                        }
+                    }
+                }
+                Payload::CustomSection(reader) if reader.name() == METADATA_SECTION => {
+                    module.metadata = Some(reader.data());
+                }
+                _ => {}
+            }
+        }
+
+        for export in exports {
+            match export.kind {
+                ExternalKind::Func | ExternalKind::FuncExact => {
+                    module.functions.insert(export.name.to_string());
+                }
+                ExternalKind::Global => {
+                    // Only block tables and strings must resolve, see `WasmArtifact::parse`.
+                    let address = globals.get(export.index as usize).copied().flatten();
+                    module.globals.insert(export.name.to_string(), address);
+                }
+                _ => {}
+            }
+        }
+        Ok(module)
+    }
+
+    /// Initial memory at `address`, at least `len` bytes up to the end of its data segment.
+    fn memory(&self, address: u32, len: usize) -> Option<&'a [u8]> {
+        self.segments.iter().find_map(|&(start, data)| {
+            let offset = address.checked_sub(start)? as usize;
+            let bytes = data.get(offset..)?;
+            (bytes.len() >= len).then_some(bytes)
+        })
+    }
+
+    /// `&[BindingDefinition]` static at `address`.
+    fn table(&self, address: u32) -> Option<Vec<BindingDefinition>> {
+        // Slice reference is a pair of `u32`s on wasm32.
+        let slice = self.memory(address, 8)?;
+        let pointer = u32::from_le_bytes(slice[..4].try_into().ok()?);
+        let len = u32::from_le_bytes(slice[4..8].try_into().ok()?) as usize;
+        let size = len * size_of::<BindingDefinition>();
+        let bytes = &self.memory(pointer, size)?[..size];
+        // Data segments are not aligned, so definitions are copied out.
+        bytes
+            .chunks_exact(size_of::<BindingDefinition>())
+            .map(|def| BindingDefinition::read_from_bytes(def).ok())
+            .collect()
+    }
+}
+
+/// Value of `i32.const` expression.
+fn const_i32(expr: &ConstExpr) -> Option<u32> {
+    match expr.get_operators_reader().read().ok()? {
+        Operator::I32Const { value } => Some(value as u32),
+        _ => None,
+    }
+}
+
+impl fmt::Display for WasmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(err) => write!(f, "invalid wasm module: {err}"),
+            Self::MissingMetadata => write!(f, "missing `{METADATA_SECTION}` section"),
+            Self::Metadata(err) => write!(f, "invalid metadata: {err}"),
+            Self::MissingStrings => write!(f, "`COLLECTED_STRINGS` is not exported"),
+            Self::Unresolved(export) => write!(f, "can't resolve address of `{export}`"),
+            Self::InvalidTable(export) => write!(f, "invalid binding table `{export}`"),
+        }
+    }
+}
+
+impl std::error::Error for WasmError {}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingExport { block, export } => {
+                write!(f, "block `{block}` doesn't export `{export}`")
+            }
+            Self::UnknownBlock { block } => {
+                write!(f, "block `{block}` is exported, but missing in metadata")
+            }
+            Self::Count {
+                export,
+                metadata,
+                exported,
+            } => write!(
+                f,
+                "`{export}` has {exported} bindings, but metadata has {metadata}"
+            ),
+            Self::Binding {
+                export,
+                index,
+                field,
+            } => write!(f, "binding {index} of `{export}` has different {field}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{borrow::Cow, num::NonZeroU8};
+
+    use wasm_encoder::{
+        CodeSection, ConstExpr, CustomSection, DataSection, ExportKind, ExportSection, Function,
+        FunctionSection, GlobalSection, GlobalType, ImportSection, Instruction, MemorySection,
+        MemoryType, Module, TypeSection, ValType,
+    };
+    use wasm_global_shared_data::{BindingDefinition, NativeBindingDefinition, IN, REQUIRED};
+    use zerocopy::IntoBytes;
+
+    use super::{Mismatch, WasmArtifact};
+    use crate::{Block, Hook, Port, WasmMetadata};
+
+    const STRINGS: u32 = 1024;
+    const DEFS: u32 = 1040;
+    const TABLE: u32 = 1056;
+
+    /// Module with block `b`, which has port `x` and no params. `unrelated`
+    /// adds an exported global initialized from an imported one.
+    fn module(metadata: &WasmMetadata, unrelated: bool) -> Vec<u8> {
+        let mut types = TypeSection::new();
+        types.ty().function([], []);
+        let mut functions = FunctionSection::new();
+        let mut code = CodeSection::new();
+        let mut exports = ExportSection::new();
+        let names = ["factory_b", "init_b", "step_b", "init_heap"];
+        for (index, name) in names.into_iter().enumerate() {
+            functions.function(0);
+            let mut body = Function::new([]);
+            body.instruction(&Instruction::End);
+            code.function(&body);
+            exports.export(name, ExportKind::Func, index as u32);
+        }
+        let mut memories = MemorySection::new();
+        memories.memory(MemoryType {
+            minimum: 1,
+            maximum: None,
+            memory64: false,
+            shared: false,
+            page_size_log2: None,
+        });
+        let typ = GlobalType {
+            val_type: ValType::I32,
+            mutable: false,
+            shared: false,
+        };
+        // Imported globals come first in the index space.
+        let mut imports = ImportSection::new();
+        let imported = u32::from(unrelated);
+        if unrelated {
+            imports.import("env", "__memory_base", typ);
+        }
+        let mut globals = GlobalSection::new();
+        let addresses = [
+            ("COLLECTED_STRINGS", STRINGS),
+            ("PORTS_b", TABLE),
+            ("PARAMS_b", TABLE + 8),
+        ];
+        for (index, (name, address)) in addresses.into_iter().enumerate() {
+            globals.global(typ, &ConstExpr::i32_const(address as i32));
+            exports.export(name, ExportKind::Global, imported + index as u32);
+        }
+        if unrelated {
+            globals.global(typ, &ConstExpr::global_get(0));
+            let index = imported + addresses.len() as u32;
+            exports.export("__heap_base", ExportKind::Global, index);
+        }
+        let def = BindingDefinition {
+            name_offset: 0,
+            flags: REQUIRED,
+            typ: 13,
+            min_size: 1,
+            max_size: NonZeroU8::new(1),
+            direction: IN,
+            name_len: 1,
+        };
+        let table = [DEFS, 1, DEFS, 0];
+        let mut data = DataSection::new();
+        data.active(0, &ConstExpr::i32_const(STRINGS as i32), *b"x");
+        data.active(
+            0,
+            &ConstExpr::i32_const(DEFS as i32),
+            def.as_bytes().to_vec(),
+        );
+        data.active(
+            0,
+            &ConstExpr::i32_const(TABLE as i32),
+            table.as_bytes().to_vec(),
+        );
+
+        let mut module = Module::new();
+        module
+            .section(&types)
+            .section(&imports)
+            .section(&functions)
+            .section(&memories)
+            .section(&globals)
+            .section(&exports)
+            .section(&code)
+            .section(&data)
+            .section(&CustomSection {
+                name: Cow::Borrowed("metadata"),
+                data: Cow::Owned(serde_json::to_vec(metadata).unwrap()),
+            });
+        module.finish()
+    }
+
+    fn block_metadata(min_size: u8) -> WasmMetadata {
+        let port = Port::try_from(NativeBindingDefinition {
+            name: "x",
+            flags: REQUIRED,
+            typ: 13,
+            min_size,
+            max_size: NonZeroU8::new(1),
+            direction: IN,
+        })
+        .unwrap();
+        WasmMetadata {
+            minimum_firmware_version: (0, 0, 0),
+            sdk_version: (0, 5, 21),
+            blocks: vec![Block {
+                name: "b".into(),
+                description: String::new(),
+                version: None,
+                semver_requirement: None,
+                ports: vec![port],
+                params: vec![],
+                block_conf: None,
+                hooks: vec![],
+            }],
+        }
+    }
+
+    #[test]
+    fn reads_exports() {
+        let artifact = WasmArtifact::parse(&module(&block_metadata(1), false)).unwrap();
+        assert_eq!(artifact.ports["b"][0].name, "x");
+        assert!(artifact.params["b"].is_empty());
+        assert_eq!(artifact.mismatches(), []);
+
+        let mut metadata = block_metadata(2);
+        metadata.blocks[0].hooks.push(Hook::Reset);
+        let artifact = WasmArtifact {
+            metadata,
+            ..WasmArtifact::parse(&module(&block_metadata(1), false)).unwrap()
+        };
+        assert_eq!(
+            artifact.mismatches(),
+            [
+                Mismatch::MissingExport {
+                    block: "b".into(),
+                    export: "reset_b".into()
+                },
+                Mismatch::Binding {
+                    export: "PORTS_b".into(),
+                    index: 0,
+                    field: "min"
+                },
+            ]
+        );
+
+        let mut metadata = block_metadata(1);
+        metadata.blocks.clear();
+        let artifact = WasmArtifact::parse(&module(&metadata, false)).unwrap();
+        assert_eq!(
+            artifact.mismatches(),
+            [Mismatch::UnknownBlock { block: "b".into() }]
+        );
+    }
+
+    #[test]
+    fn skips_unrelated_globals() {
+        let artifact = WasmArtifact::parse(&module(&block_metadata(1), true)).unwrap();
+        assert_eq!(artifact.ports["b"][0].name, "x");
+        assert_eq!(artifact.mismatches(), []);
+    }
+}
@@ -0,0 +1,530 @@
+/*!
+Trait-based block definition, an alternative to free functions passed to
+`register_block!`.
+
+Implement [`Block`] and derive it to get the same `factory_*`, `init_*` and
+`step_*` exports. Ports and params are parsed before `init` and `step` are called,
+and config is read from `FactoryInput` before `new`. If ports or params can't
+be parsed, the error is logged and returned as [`BlockError::Parse`] code.
+
+`ports!`, `params!` and `Config` for the block must be declared before the
+derive, as it reads their metadata. By default only one instance of the block
+can be created, use `#[capacity = N]` to allow more.
+
+Instances are dropped on `deinit`. `reset` and `reconfigure` hooks are
+exported only when listed in `#[hooks(reset, reconfigure)]`.
+
+# Example
+
+```rust
+use micrortu_sdk::{params, ports, Block, FactoryInput, StepResult};
+
+ports! {
+    #[block_names(counter)]
+    pub struct Ports {
+        count: TI13 InOut 1 1,
+    }
+}
+params! {
+    #[block_names(counter)]
+    pub struct Params {}
+}
+
+#[derive(Block)]
+#[block_names(counter)]
+pub struct Counter;
+
+impl Block for Counter {
+    type Ports<'a> = Ports<'a>;
+    type Params<'a> = Params<'a>;
+    type Config = ();
+
+    fn new(_: &FactoryInput, (): ()) -> Self {
+        Self
+    }
+
+    fn step(&mut self, ports: Ports<'_>, _: Params<'_>) -> StepResult {
+        ports.count.value += 1.;
+        0
+    }
+}
+```
+*/
+
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{BlockError, BlockPorts, Config, FactoryInput, IntoStepResult, Shared, StepResult};
+
+pub trait Block: Sized + 'static {
+    type Ports<'a>: BlockPorts<'a>;
+    type Params<'a>: BlockPorts<'a>;
+    /// Block configuration. Use `()` if block has none.
+    type Config: Config;
+
+    /// Creates block instance. Called once per instance from the factory.
+    fn new(input: &FactoryInput, config: Self::Config) -> Self;
+
+    /// Called once before the first `step`.
+    fn init(&mut self, ports: Self::Ports<'_>, params: Self::Params<'_>) -> StepResult {
+        _ = (ports, params);
+        0
+    }
+
+    fn step(&mut self, ports: Self::Ports<'_>, params: Self::Params<'_>) -> StepResult;
+
+    /// Called before the instance is dropped and its storage released.
+    fn deinit(&mut self) {}
+
+    /// Called on process restart without reloading the block.
+    /// Exported only if listed in `#[hooks(...)]`.
+    fn reset(&mut self, ports: Self::Ports<'_>, params: Self::Params<'_>) -> StepResult {
+        _ = (ports, params);
+        0
+    }
+
+    /// Called to apply new config without recreating the block.
+    /// Exported only if listed in `#[hooks(...)]`.
+    fn reconfigure(&mut self, input: &FactoryInput, config: Self::Config) -> StepResult {
+        _ = (input, config);
+        0
+    }
+}
+
+impl Config for () {
+    #[cfg(feature = "std")]
+    fn config_schema() -> micrortu_build_utils::BlockConf {
+        micrortu_build_utils::BlockConf {
+            required: false,
+            fields: Default::default(),
+            descriptions: Default::default(),
+        }
+    }
+}
+
+/// Fixed-capacity storage for block instances.
+///
+/// Used by `#[derive(Block)]` and `register_block!` with `capacity = N`, so
+/// the factory can be called up to `N` times, each instance getting its own
+/// state. Unlike `StaticCell`, it doesn't panic when full.
+pub struct BlockPool<T, const N: usize> {
+    taken: [AtomicBool; N],
+    values: [UnsafeCell<MaybeUninit<T>>; N],
+}
+
+// Safety: each value is handed out at most once, guarded by its `taken` flag.
+unsafe impl<T: Send, const N: usize> Sync for BlockPool<T, N> {}
+
+impl<T, const N: usize> BlockPool<T, N> {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            taken: [const { AtomicBool::new(false) }; N],
+            values: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+        }
+    }
+
+    /// Moves `value` into a free slot. Returns `None` if the pool is full.
+    #[allow(clippy::mut_from_ref)]
+    pub fn try_alloc(&'static self, value: T) -> Option<&'static mut T> {
+        let idx = self
+            .taken
+            .iter()
+            .position(|taken| !taken.swap(true, Ordering::AcqRel))?;
+        // SAFETY: the slot was not taken before, so no other reference exists.
+        let slot = unsafe { &mut *self.values[idx].get() };
+        Some(slot.write(value))
+    }
+
+    /// Drops `block` and makes its slot available again.
+    /// Returns `false` if `block` doesn't belong to the pool.
+    ///
+    /// # Safety
+    ///
+    /// `block` must not be used after this call.
+    pub unsafe fn release(&'static self, block: &mut T) -> bool {
+        let ptr = core::ptr::from_mut(block).cast::<MaybeUninit<T>>();
+        let Some(idx) = self.values.iter().position(|value| value.get() == ptr) else {
+            return false;
+        };
+        // SAFETY: slot is taken and initialized, caller guarantees it's not used anymore.
+        unsafe { core::ptr::drop_in_place(block) };
+        self.taken[idx].store(false, Ordering::Release);
+        true
+    }
+
+    /// Number of instances currently allocated.
+    pub fn len(&self) -> usize {
+        self.taken
+            .iter()
+            .filter(|taken| taken.load(Ordering::Acquire))
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T, const N: usize> Default for BlockPool<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Moves instance created by a factory into `pool`.
+/// Logs an error and returns `None` if the pool is full.
+#[doc(hidden)]
+pub fn alloc<T, const N: usize>(
+    pool: &'static BlockPool<T, N>,
+    value: Option<T>,
+) -> Option<&'static mut T> {
+    let block = pool.try_alloc(value?);
+    if block.is_none() {
+        crate::error!("Block instance pool is full, capacity is {}", N);
+    }
+    block
+}
+
+/// Releases instance allocated by `alloc`.
+#[doc(hidden)]
+pub fn release<T, const N: usize>(pool: &'static BlockPool<T, N>, block: &mut T) {
+    // SAFETY: firmware doesn't use the instance after `deinit`.
+    if !unsafe { pool.release(block) } {
+        crate::error!("Released block instance doesn't belong to the pool");
+    }
+}
+
+/// Reads `B::Config` from `input`. Missing config is read as zeroed.
+fn read_config<B: Block>(input: &FactoryInput) -> Option<B::Config> {
+    let len = (input.config_len as usize).min(input.config.0.len());
+    if len == 0 {
+        return Some(zerocopy::FromZeros::new_zeroed());
+    }
+    match <B::Config as zerocopy::FromBytes>::read_from_prefix(&input.config.0[..len]) {
+        Ok((config, _)) => Some(config),
+        Err(_) => {
+            let expected = core::mem::size_of::<B::Config>();
+            crate::error!("Invalid config: got {} bytes, expected {}", len, expected);
+            None
+        }
+    }
+}
+
+#[doc(hidden)]
+pub fn factory<B: Block>(input: &FactoryInput) -> Option<B> {
+    let config = read_config::<B>(input)?;
+    Some(B::new(input, config))
+}
+
+/// Parses ports and params of `B`, logging the error on failure.
+fn parse<B: Block>(shared: &mut Shared) -> Result<(B::Ports<'_>, B::Params<'_>), StepResult> {
+    let ports = B::Ports::parse_fallible(&mut shared.latched_ports[..]);
+    let params = B::Params::parse_fallible(&mut shared.latched_params[..]);
+    match (ports, params) {
+        (Ok(ports), Ok(params)) => Ok((ports, params)),
+        (Err(err), _) | (_, Err(err)) => Err(Err::<(), _>(err).into_step_result()),
+    }
+}
+
+#[doc(hidden)]
+pub fn init<B: Block>(shared: &mut Shared, block: &mut B) -> StepResult {
+    match parse::<B>(shared) {
+        Ok((ports, params)) => block.init(ports, params),
+        Err(code) => code,
+    }
+}
+
+#[doc(hidden)]
+pub fn step<B: Block>(shared: &mut Shared, block: &mut B) -> StepResult {
+    match parse::<B>(shared) {
+        Ok((ports, params)) => block.step(ports, params),
+        Err(code) => code,
+    }
+}
+
+#[doc(hidden)]
+pub fn deinit<B: Block>(block: &mut B) {
+    block.deinit();
+}
+
+#[doc(hidden)]
+pub fn reset<B: Block>(shared: &mut Shared, block: &mut B) -> StepResult {
+    match parse::<B>(shared) {
+        Ok((ports, params)) => block.reset(ports, params),
+        Err(code) => code,
+    }
+}
+
+#[doc(hidden)]
+pub fn reconfigure<B: Block>(input: &FactoryInput, block: &mut B) -> StepResult {
+    match read_config::<B>(input) {
+        Some(config) => block.reconfigure(input, config),
+        None => BlockError::InvalidConfig.code(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ie_base::M_ME_NE_1;
+
+    use crate::{
+        params, ports, register_block,
+        testing::{factory_input, factory_input_with_config, BlockHarness, HarnessError},
+        Block, BlockPorts, FactoryInput, Shared, StepResult,
+    };
+
+    #[derive(zerocopy::IntoBytes, zerocopy::FromBytes, zerocopy::Immutable, crate::Config)]
+    #[block_names(trait_accumulator)]
+    #[repr(C)]
+    pub struct AccumulatorConfig {
+        step: f32,
+    }
+
+    ports! {
+        #[block_names(trait_accumulator)]
+        pub struct Ports {
+            total: TI13 InOut 1 1,
+        }
+    }
+    params! {
+        #[block_names(trait_accumulator)]
+        pub struct Params {
+            initial: TI13 In 1 1,
+        }
+    }
+
+    #[derive(Block)]
+    #[block_names(trait_accumulator)]
+    #[capacity = 2]
+    #[hooks(reset, reconfigure)]
+    #[version = "1.1.0"]
+    pub struct Accumulator {
+        step: f32,
+    }
+
+    impl Block for Accumulator {
+        type Ports<'a> = Ports<'a>;
+        type Params<'a> = Params<'a>;
+        type Config = AccumulatorConfig;
+
+        fn new(_: &FactoryInput, config: AccumulatorConfig) -> Self {
+            Self { step: config.step }
+        }
+
+        fn init(&mut self, ports: Ports<'_>, params: Params<'_>) -> StepResult {
+            ports.total.value = params.initial.value;
+            0
+        }
+
+        fn step(&mut self, ports: Ports<'_>, _: Params<'_>) -> StepResult {
+            ports.total.value += self.step;
+            0
+        }
+
+        fn reset(&mut self, ports: Ports<'_>, params: Params<'_>) -> StepResult {
+            self.init(ports, params)
+        }
+
+        fn reconfigure(&mut self, _: &FactoryInput, config: AccumulatorConfig) -> StepResult {
+            self.step = config.step;
+            0
+        }
+    }
+
+    ports! {
+        #[block_names(pooled_counter)]
+        pub struct CounterPorts {}
+    }
+    params! {
+        #[block_names(pooled_counter)]
+        pub struct CounterParams {}
+    }
+
+    pub struct Counter(u32);
+
+    pub fn counter_factory(_: &FactoryInput) -> Option<Counter> {
+        Some(Counter(0))
+    }
+
+    pub fn counter_step(_: &mut Shared, counter: &mut Counter) -> StepResult {
+        counter.0 += 1;
+        0
+    }
+
+    register_block!(
+        Counter,
+        pooled_counter,
+        counter_factory,
+        counter_step,
+        counter_step,
+        capacity = 3,
+        version = "2.0.0",
+        firmware = ">=0.16, <2"
+    );
+
+    pub struct Latch(f32);
+
+    ports! {
+        #[block_names(hooked_latch)]
+        pub struct LatchPorts {
+            x: TI13 In 1 1,
+            total: TI13 InOut 1 1,
+        }
+    }
+    params! {
+        #[block_names(hooked_latch)]
+        pub struct LatchParams {}
+    }
+
+    fn gain(input: &FactoryInput) -> Option<f32> {
+        if input.config_len == 0 {
+            return Some(1.);
+        }
+        Some(f32::from_le_bytes(input.config.0[..4].try_into().ok()?))
+    }
+
+    pub fn latch_factory(input: &FactoryInput) -> Option<Latch> {
+        gain(input).map(Latch)
+    }
+
+    pub fn latch_step(shared: &mut Shared, latch: &mut Latch) -> StepResult {
+        let ports = LatchPorts::parse(&mut shared.latched_ports[..]);
+        ports.total.value += ports.x.value * latch.0;
+        0
+    }
+
+    pub fn latch_reset(shared: &mut Shared, _: &mut Latch) -> StepResult {
+        let ports = LatchPorts::parse(&mut shared.latched_ports[..]);
+        ports.total.value = 0.;
+        0
+    }
+
+    pub fn latch_reconfigure(input: &FactoryInput, latch: &mut Latch) -> StepResult {
+        match gain(input) {
+            Some(gain) => {
+                latch.0 = gain;
+                0
+            }
+            None => 1,
+        }
+    }
+
+    register_block!(
+        Latch,
+        hooked_latch,
+        latch_factory,
+        latch_step,
+        latch_step,
+        capacity = 1,
+        reset = latch_reset,
+        reconfigure = latch_reconfigure,
+    );
+
+    fn measurement(value: f32) -> M_ME_NE_1 {
+        M_ME_NE_1 {
+            value,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pooled_instances() {
+        let mut instances: Vec<_> = (0..3)
+            .map(|_| BlockHarness::new(BLOCK_pooled_counter, &factory_input(100)).unwrap())
+            .collect();
+        for (idx, harness) in instances.iter_mut().enumerate() {
+            for _ in 0..=idx {
+                assert_eq!(harness.step().unwrap(), 0);
+            }
+        }
+        let steps: Vec<u32> = instances.iter_mut().map(|it| it.block().0).collect();
+        assert_eq!(steps, [1, 2, 3]);
+
+        let fourth = BlockHarness::new(BLOCK_pooled_counter, &factory_input(100));
+        assert_eq!(fourth.err(), Some(HarnessError::FactoryFailed));
+
+        instances.remove(1).deinit();
+        let mut fourth = BlockHarness::new(BLOCK_pooled_counter, &factory_input(100)).unwrap();
+        assert_eq!(fourth.block().0, 0);
+        assert!(BLOCK_pooled_counter.reset.is_none());
+
+        drop((instances, fourth));
+        let instances: Vec<_> = (0..3)
+            .map(|_| BlockHarness::new(BLOCK_pooled_counter, &factory_input(100)))
+            .collect();
+        assert!(instances.iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn derived_block() {
+        let config = AccumulatorConfig { step: 0.5 };
+        let input = factory_input_with_config(100, config).unwrap();
+        let mut harness = BlockHarness::new(BLOCK_trait_accumulator, &input).unwrap();
+        harness
+            .set_port("total", [measurement(0.)])
+            .unwrap()
+            .set_param("initial", [measurement(10.)])
+            .unwrap();
+
+        assert_eq!(harness.init().unwrap(), 0);
+        assert_eq!(harness.step().unwrap(), 0);
+        assert_eq!(harness.step().unwrap(), 0);
+        let total = harness.port("total").unwrap()[0].extract_ti::<M_ME_NE_1>();
+        assert_eq!(total.map(|ie| ie.value), Some(11.));
+
+        let mut second = BlockHarness::new(BLOCK_trait_accumulator, &factory_input(100)).unwrap();
+        assert_eq!(second.block().step, 0.);
+        assert_eq!(harness.block().step, 0.5);
+
+        let third = BlockHarness::new(BLOCK_trait_accumulator, &factory_input(100));
+        assert_eq!(third.err(), Some(HarnessError::FactoryFailed));
+        #[cfg(not(feature = "micrortu_sdk_internal"))]
+        {
+            let logs = crate::log::take_logs();
+            assert_eq!(
+                logs.last().map(|(level, msg)| (*level, msg.as_str())),
+                Some((1, "Block instance pool is full, capacity is 2"))
+            );
+        }
+
+        assert_eq!(harness.reset().unwrap(), 0);
+        let total = harness.port("total").unwrap()[0].extract_ti::<M_ME_NE_1>();
+        assert_eq!(total.map(|ie| ie.value), Some(10.));
+        let input = factory_input_with_config(100, AccumulatorConfig { step: 2. }).unwrap();
+        assert_eq!(harness.reconfigure(&input).unwrap(), 0);
+        assert_eq!(harness.block().step, 2.);
+
+        second.deinit();
+        assert!(BlockHarness::new(BLOCK_trait_accumulator, &factory_input(100)).is_ok());
+    }
+
+    #[test]
+    fn register_block_hooks() {
+        let mut harness = BlockHarness::new(BLOCK_hooked_latch, &factory_input(100)).unwrap();
+        harness
+            .set_port("x", [measurement(3.)])
+            .unwrap()
+            .set_port("total", [measurement(5.)])
+            .unwrap();
+        let total = |harness: &BlockHarness<Latch>| {
+            let total = harness.port("total").unwrap()[0].extract_ti::<M_ME_NE_1>();
+            total.map(|ie| ie.value)
+        };
+        assert_eq!(harness.step().unwrap(), 0);
+        assert_eq!(total(&harness), Some(8.));
+
+        assert_eq!(harness.reset().unwrap(), 0);
+        assert_eq!(total(&harness), Some(0.));
+
+        let input = factory_input_with_config(100, AccumulatorConfig { step: 2. }).unwrap();
+        assert_eq!(harness.reconfigure(&input).unwrap(), 0);
+        assert_eq!(harness.step().unwrap(), 0);
+        assert_eq!(total(&harness), Some(6.));
+        assert!(BLOCK_hooked_latch.reconfigure.is_some());
+        harness.deinit();
+    }
+}
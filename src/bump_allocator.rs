@@ -143,7 +143,7 @@ mod test {
         let b = allocator.alloc::<&'static [u8]>(&[]);
         let c = allocator.alloc::<u32>(3);
         assert_eq!(*a, 1);
-        assert_eq!(*b, &[]);
+        assert!(b.is_empty());
         assert_eq!(*c, 3);
     }
 
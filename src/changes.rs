@@ -0,0 +1,127 @@
+/*!
+Change detection for inputs, used by `#[track_changes]` in `ports!`/`params!`.
+
+For a struct `Ports` with `#[track_changes]`, a `PortsSnapshot` is generated.
+It is stored in the block and updated at the end of each step. It has
+`<name>_changed(&ports)` and `<name>_previous()` accessors for every input.
+Values are compared bytewise, so a change of quality descriptor is a change too.
+
+# Example
+
+```ignore
+ports! {
+    #[block_names(edge)]
+    #[track_changes]
+    pub struct Ports {
+        cmd: TI45 In 1 1,
+    }
+}
+
+pub fn step(shared: &mut Shared, block: &mut Edge) -> StepResult {
+    let ports = Ports::parse(&mut shared.latched_ports[..]);
+    if block.snapshot.cmd_changed(&ports) {
+        // ...
+    }
+    block.snapshot.update(&ports);
+    0
+}
+```
+*/
+
+use zerocopy::{Immutable, IntoBytes};
+
+/// Elements of a binding on the last update, up to `N` of them.
+#[derive(Debug, Clone, Copy)]
+pub struct Previous<T, const N: usize> {
+    values: [T; N],
+    len: usize,
+    stored: bool,
+}
+
+impl<T: Copy + Default + IntoBytes + Immutable, const N: usize> Previous<T, N> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            values: [T::default(); N],
+            len: 0,
+            stored: false,
+        }
+    }
+
+    /// Stored elements, `None` before the first update.
+    #[must_use]
+    pub fn get(&self) -> Option<&[T]> {
+        self.stored.then(|| &self.values[..self.len])
+    }
+
+    /// Whether `current` differs from stored elements. `true` before the first update.
+    #[must_use]
+    pub fn changed(&self, current: &[T]) -> bool {
+        self.get()
+            .is_none_or(|previous| previous.as_bytes() != current.as_bytes())
+    }
+
+    /// Stores `current`, truncated to `N` elements.
+    pub fn update(&mut self, current: &[T]) {
+        let len = current.len().min(N);
+        self.values[..len].copy_from_slice(&current[..len]);
+        self.len = len;
+        self.stored = true;
+    }
+}
+
+impl<T: Copy + Default + IntoBytes + Immutable, const N: usize> Default for Previous<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ie_base::{QualityDescriptor, M_ME_NE_1, M_SP_NA_1, QDS};
+
+    use crate::ports;
+
+    ports! {
+        #[block_names(tracked)]
+        #[track_changes]
+        pub struct Ports {
+            x: TI13 In 1 1,
+            flags: TI1 In 1 3 ?,
+            y: TI13 Out 1 1,
+        }
+    }
+
+    #[test]
+    fn snapshot() {
+        let mut x = M_ME_NE_1::default();
+        let flags = [M_SP_NA_1::from(true)];
+        let mut y = M_ME_NE_1::default();
+        let mut snapshot = PortsSnapshot::new();
+
+        let ports = Ports {
+            x: &x,
+            flags: Some(&flags),
+            y: &mut y,
+            _marker: core::marker::PhantomData,
+        };
+        assert!(snapshot.x_changed(&ports));
+        assert_eq!(snapshot.x_previous(), None);
+        snapshot.update(&ports);
+        assert!(!snapshot.changed(&ports));
+        assert_eq!(snapshot.flags_previous().map(<[_]>::len), Some(1));
+
+        x.qds = QDS::INVALID;
+        let ports = Ports {
+            x: &x,
+            flags: None,
+            y: &mut y,
+            _marker: core::marker::PhantomData,
+        };
+        assert!(snapshot.x_changed(&ports));
+        assert!(snapshot.flags_changed(&ports));
+        snapshot.update(&ports);
+        assert!(snapshot.x_previous().unwrap().qds.iv());
+        assert_eq!(snapshot.flags_previous(), Some(&[][..]));
+    }
+}
@@ -0,0 +1,297 @@
+/*!
+Typed error codes for [`StepResult`].
+
+Block functions passed to `register_block!` can return either a raw
+[`StepResult`] or `Result<(), E>` where `E: Into<BlockError>`. The generated
+exports log the error and return its [`BlockError::code`] to the firmware.
+
+# Example
+
+```rust
+use micrortu_sdk::{BlockError, ParseError};
+
+fn step() -> Result<(), BlockError> {
+    Err(ParseError::NotEnoughData)?
+}
+
+assert_eq!(step().unwrap_err().code(), 0x101);
+```
+*/
+
+use ufmt::derive::uDebug;
+
+use crate::{AllocError, ParseError, StepResult};
+
+/// Error returned by a block. Converted to a non-zero [`StepResult`].
+///
+/// Codes `1..0x2_0000` are reserved for the SDK, user-defined codes start
+/// at `0x2_0000`. Parse errors take `0x100..0x1_0100`, with
+/// [`ParseError::kind`] in the low byte and index of the failed binding plus
+/// one above it, up to `0x100` for binding 255.
+///
+/// Not `#[repr(i32)]`: parse errors and user codes carry data, so the code
+/// is computed by [`BlockError::code`] instead of being the discriminant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uDebug)]
+pub enum BlockError {
+    /// Input value has bad quality and can't be processed.
+    BadQuality,
+    /// Block configuration is invalid.
+    InvalidConfig,
+    /// Arithmetic error, like overflow or division by zero.
+    Arithmetic,
+    /// Out of memory.
+    OutOfMemory,
+    /// Ports or params can't be parsed.
+    Parse(ParseError),
+    /// Block-specific error code.
+    User(u16),
+}
+
+impl BlockError {
+    /// Code reported to the firmware.
+    #[must_use]
+    pub fn code(self) -> StepResult {
+        match self {
+            Self::BadQuality => 1,
+            Self::InvalidConfig => 2,
+            Self::Arithmetic => 3,
+            Self::OutOfMemory => 4,
+            Self::Parse(err) => {
+                let binding = err.binding().map_or(0, i32::from);
+                0x100 * (binding + 1) + i32::from(err.kind())
+            }
+            Self::User(code) => 0x2_0000 + i32::from(code),
+        }
+    }
+
+    /// Inverse of [`BlockError::code`]. Returns `None` for `0` and unknown codes.
+    #[must_use]
+    pub fn from_code(code: StepResult) -> Option<Self> {
+        Some(match code {
+            1 => Self::BadQuality,
+            2 => Self::InvalidConfig,
+            3 => Self::Arithmetic,
+            4 => Self::OutOfMemory,
+            0x100..0x1_0100 => {
+                let [kind, ..] = code.to_le_bytes();
+                let binding = u8::try_from((code >> 8) - 1).ok();
+                let err = ParseError::from_kind(kind, None)
+                    .filter(|_| binding == Some(0))
+                    .or_else(|| ParseError::from_kind(kind, binding));
+                Self::Parse(err?)
+            }
+            _ => Self::User(u16::try_from(code.checked_sub(0x2_0000)?).ok()?),
+        })
+    }
+}
+
+impl From<ParseError> for BlockError {
+    fn from(err: ParseError) -> Self {
+        Self::Parse(err)
+    }
+}
+
+impl From<AllocError> for BlockError {
+    fn from(_: AllocError) -> Self {
+        Self::OutOfMemory
+    }
+}
+
+impl core::fmt::Display for BlockError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::BadQuality => write!(f, "input has bad quality"),
+            Self::InvalidConfig => write!(f, "invalid config"),
+            Self::Arithmetic => write!(f, "arithmetic error"),
+            Self::OutOfMemory => write!(f, "out of memory"),
+            Self::Parse(err) => write!(f, "failed to parse bindings: {err:?}"),
+            Self::User(code) => write!(f, "block error {code}"),
+        }
+    }
+}
+
+impl core::error::Error for BlockError {}
+
+/// Return type of block functions, convertible to [`StepResult`].
+pub trait IntoStepResult {
+    fn into_step_result(self) -> StepResult;
+}
+
+impl IntoStepResult for StepResult {
+    fn into_step_result(self) -> StepResult {
+        self
+    }
+}
+
+/// Logs the error before returning its code.
+impl<E: Into<BlockError>> IntoStepResult for Result<(), E> {
+    fn into_step_result(self) -> StepResult {
+        match self {
+            Ok(()) => 0,
+            Err(err) => {
+                let err = err.into();
+                crate::error!("Block failed: {:?}", err);
+                err.code()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BlockError;
+    use crate::{
+        params, ports, register_block, AllocError, BlockPorts, FactoryInput, ParseError, Shared,
+    };
+
+    ports! {
+        #[block_names(fallible)]
+        pub struct Ports {
+            x: TI13 In 1 1,
+        }
+    }
+    params! {
+        #[block_names(fallible)]
+        pub struct Params {}
+    }
+
+    ports! {
+        #[block_names(not_zeroed)]
+        #[not_zeroed(error)]
+        pub struct StrictPorts {
+            x: TI13 In 1 1,
+            y: TI13 Out 1 1,
+        }
+    }
+
+    ports! {
+        #[block_names(not_zeroed_log)]
+        pub struct LenientPorts {
+            y: TI13 Out 1 1,
+        }
+    }
+
+    pub struct Fallible;
+
+    pub fn factory(_: &FactoryInput) -> Option<Fallible> {
+        Some(Fallible)
+    }
+
+    pub fn init(_: &mut Shared, _: &mut Fallible) -> Result<(), AllocError> {
+        Ok(())
+    }
+
+    pub fn step(shared: &mut Shared, _: &mut Fallible) -> Result<(), BlockError> {
+        let ports = Ports::parse_fallible(&mut shared.latched_ports[..])?;
+        if ports.x.value < 0. {
+            return Err(BlockError::User(7));
+        }
+        Ok(())
+    }
+
+    register_block!(Fallible, fallible, factory, init, step, capacity = 1);
+
+    #[test]
+    fn codes_round_trip() {
+        let errors = [
+            BlockError::BadQuality,
+            BlockError::InvalidConfig,
+            BlockError::Arithmetic,
+            BlockError::OutOfMemory,
+            BlockError::Parse(ParseError::NotTerminated),
+            BlockError::Parse(ParseError::MultiplePointsForSingular),
+            BlockError::Parse(ParseError::NotEnoughElements { binding: 0 }),
+            BlockError::Parse(ParseError::NotZeroed { binding: 169 }),
+            BlockError::Parse(ParseError::NotZeroed { binding: 255 }),
+            BlockError::User(0),
+            BlockError::User(u16::MAX),
+        ];
+        for err in errors {
+            assert_ne!(err.code(), 0);
+            assert_eq!(BlockError::from_code(err.code()), Some(err));
+        }
+        assert_eq!(BlockError::from_code(0), None);
+        assert_eq!(BlockError::from_code(-1), None);
+        assert_eq!(BlockError::from_code(0x10a), None);
+        assert_eq!(BlockError::from_code(0x201), None);
+        assert_eq!(BlockError::from_code(0x1_0100), None);
+        assert_eq!(BlockError::from_code(0x1_ffff), None);
+    }
+
+    #[test]
+    fn last_binding_is_not_user_code() {
+        let err = BlockError::Parse(ParseError::NotZeroed { binding: 255 });
+        assert_eq!(err.code(), 0x1_0009);
+        assert!(err.code() < BlockError::User(0).code());
+    }
+
+    #[test]
+    #[cfg(not(feature = "micrortu_sdk_internal"))]
+    fn result_is_logged() {
+        use super::IntoStepResult;
+
+        assert_eq!(Ok::<(), BlockError>(()).into_step_result(), 0);
+        assert!(crate::log::take_logs().is_empty());
+
+        assert_eq!(Err(AllocError).into_step_result(), 4);
+        assert_eq!(
+            crate::log::take_logs(),
+            [(1, "Block failed: OutOfMemory".into())]
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "micrortu_sdk_internal"))]
+    fn exports_convert_result() {
+        use crate::testing::{factory_input, BlockHarness};
+
+        let mut harness = BlockHarness::new(BLOCK_fallible, &factory_input(100)).unwrap();
+        assert_eq!(harness.init().unwrap(), 0);
+        assert_eq!(
+            harness.step().unwrap(),
+            BlockError::Parse(ParseError::NotEnoughElements { binding: 0 }).code()
+        );
+
+        let x = ie_base::M_ME_NE_1 {
+            value: -1.,
+            ..Default::default()
+        };
+        harness.set_port("x", [x]).unwrap();
+        assert_eq!(harness.step().unwrap(), 0x2_0007);
+        let logs = crate::log::take_logs();
+        assert_eq!(
+            logs.last().map(|(_, msg)| msg.as_str()),
+            Some("Block failed: User(7)")
+        );
+        assert!(logs.contains(&(
+            1,
+            "Block failed: Parse(NotEnoughElements { binding: 0 })".into()
+        )));
+    }
+
+    #[test]
+    #[cfg(not(feature = "micrortu_sdk_internal"))]
+    fn binding_errors() {
+        let mut source = [0, 5, 0, 5, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+        assert_eq!(
+            StrictPorts::parse_fallible(&mut source).err(),
+            Some(ParseError::NotZeroed { binding: 1 })
+        );
+        let mut source = [0, 0, 0, 5, 0, 0, 0, 0, 0];
+        assert_eq!(
+            StrictPorts::parse_fallible(&mut source).err(),
+            Some(ParseError::NotEnoughElements { binding: 0 })
+        );
+
+        crate::log::take_logs();
+        for _ in 0..2 {
+            let mut source = [0, 5, 1, 0, 0, 0, 0];
+            let ports = LenientPorts::parse_fallible(&mut source).unwrap();
+            assert_eq!(zerocopy::IntoBytes::as_bytes(ports.y), [0; 5]);
+        }
+        assert_eq!(
+            crate::log::take_logs(),
+            [(2, "OUT binding `y` is not zeroed".into())]
+        );
+    }
+}
@@ -1,5 +1,7 @@
 #![doc = include_str!("../README.md")]
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "testing")), no_std)]
+
+extern crate self as micrortu_sdk;
 
 #[allow(dead_code)]
 union Exported {
@@ -10,43 +12,87 @@ union Exported {
 #[no_mangle]
 static mut SHARED: MaybeUninit<Exported> = MaybeUninit::zeroed();
 
+pub mod block;
 pub mod bump_allocator;
+pub mod changes;
+pub mod error;
 mod getters_setters;
 pub mod log;
+pub mod panic;
+pub mod range;
+pub mod reflect;
+#[cfg(all(any(test, feature = "testing"), not(target_arch = "wasm32")))]
+pub mod testing;
 pub mod trap_err;
+pub mod value;
 
 use core::mem::{ManuallyDrop, MaybeUninit};
 
 pub use getters_setters::*;
 pub use ie_base;
 /// Macros for generating parser of arguments block requires.
-pub use ie_representation_derive::{finalize, params, ports, register_block, Config};
+pub use ie_representation_derive::{finalize, params, ports, register_block, Block, Config};
 pub use wasm_global_shared_data;
 
-pub use bump_allocator::{BumpAllocator, AllocError};
+pub use block::Block;
+pub use bump_allocator::{AllocError, BumpAllocator};
+pub use error::{BlockError, IntoStepResult};
 pub use ie_base::IEBuf;
 pub use wasm_global_shared_data::{
-    BindingDefinition, Direction, FactoryInput, NativeBindingDefinition, ParseError, Shared,
-    StepResult, BINDINGS_BYTES_CAP, REQUIRED,
+    write_bindings, BindingDefinition, BindingView, BindingsReader, BindingsWriter, Direction,
+    EncodeError, FactoryInput, NativeBindingDefinition, ParseError, Shared, StepResult, ANY,
+    BINDINGS_BYTES_CAP, REQUIRED,
 };
 // these re-exports are used by `ie_representation_derive::register_block! macro`
 pub use wasm_global_shared_data::{IN, IN_OUT, OUT};
 
 pub use ufmt;
 
+/// Routes the `log` crate to the firmware log, see [`log::Logger`].
+/// Records more verbose than `Info` are discarded.
+#[cfg(not(feature = "micrortu_sdk_internal"))]
+pub fn init_logger() {
+    init_logger_with_level(::log::LevelFilter::Info);
+}
+
+/// Same as [`init_logger`], with `level` as max level.
+#[cfg(not(feature = "micrortu_sdk_internal"))]
+pub fn init_logger_with_level(level: ::log::LevelFilter) {
+    log::init(level);
+}
+
+#[cfg(feature = "micrortu_sdk_internal")]
 pub fn init_logger() {}
 
 #[doc(hidden)]
+#[track_caller]
 pub fn wasm_unwrap<T>(v: Option<T>) -> T {
     match v {
         Some(v) => v,
-        #[cfg(target_arch = "wasm32")]
+        #[cfg(all(target_arch = "wasm32", not(feature = "panic-handler")))]
         None => ::core::arch::wasm32::unreachable(),
-        #[cfg(not(target_arch = "wasm32"))]
+        #[cfg(any(not(target_arch = "wasm32"), feature = "panic-handler"))]
         None => unreachable!(),
     }
 }
 
+/// Reports `err` of [`BlockPorts::parse`]: panics with the `panic-handler`
+/// feature or off wasm, otherwise logs it and traps.
+/// Called by parsers generated with `ports!` and `params!`.
+#[doc(hidden)]
+#[track_caller]
+pub fn parse_failed(err: ParseError) -> ! {
+    #[cfg(all(target_arch = "wasm32", not(feature = "panic-handler")))]
+    {
+        crate::error!("Failed to parse bindings: {:?}", err);
+        ::core::arch::wasm32::unreachable()
+    }
+    #[cfg(any(not(target_arch = "wasm32"), feature = "panic-handler"))]
+    {
+        panic!("Failed to parse bindings: {:?}", err)
+    }
+}
+
 pub trait Config: zerocopy::FromBytes + zerocopy::IntoBytes {
     #[cfg(feature = "std")]
     fn config_schema() -> micrortu_build_utils::BlockConf;
@@ -57,19 +103,159 @@ pub trait BlockPorts<'a>: Sized {
     fn parse(source: &'a mut [u8]) -> Self {
         match Self::parse_fallible(source) {
             Ok(binds) => binds,
-            Err(_err) => {
-                // error!("Failed to parse bindings: {:?}", err);
-                #[cfg(target_arch = "wasm32")]
-                {
-                    ::core::arch::wasm32::unreachable()
-                }
-                #[cfg(not(target_arch = "wasm32"))]
-                {
-                    panic!("Failed to parse bindings: {:?}", _err)
-                }
-            }
+            Err(err) => parse_failed(err),
         }
     }
     #[cfg(not(target_arch = "wasm32"))]
     fn report() -> &'static [NativeBindingDefinition<'static>];
 }
+
+/// A block registered with `register_block!`, as seen from native code.
+///
+/// `register_block!` emits a `BLOCK_{name}` constant of this type, so the block
+/// can be driven without a wasm runtime, e.g. by `testing::BlockHarness`.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct RegisteredBlock<B: 'static> {
+    pub name: &'static str,
+    pub factory: fn(&FactoryInput) -> Option<&'static mut B>,
+    pub init: fn(&mut Shared, &mut B) -> StepResult,
+    pub step: fn(&mut Shared, &mut B) -> StepResult,
+    pub deinit: Option<fn(&mut B)>,
+    pub reset: Option<fn(&mut Shared, &mut B) -> StepResult>,
+    pub reconfigure: Option<fn(&FactoryInput, &mut B) -> StepResult>,
+    pub ports: &'static [NativeBindingDefinition<'static>],
+    pub params: &'static [NativeBindingDefinition<'static>],
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<B> Clone for RegisteredBlock<B> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<B> Copy for RegisteredBlock<B> {}
+
+#[cfg(test)]
+mod test {
+    use ie_base::{SmallIE, M_DP_NA_1, M_ME_NE_1, M_SP_NA_1};
+
+    use crate::{
+        params, ports, write_bindings, BlockPorts, ParseError, Shared, BINDINGS_BYTES_CAP, REQUIRED,
+    };
+
+    ports! {
+        #[block_names(unbound)]
+        pub struct UnboundPorts {
+            x: TI13 In 1 1,
+            y: TI13 In 1 2 ?,
+        }
+    }
+
+    ports! {
+        #[block_names(any_copy)]
+        pub struct AnyPorts {
+            src: ANY(TI1 | TI13) In 1 1,
+            dst: ANY Out 1 1,
+        }
+    }
+
+    ports! {
+        #[block_names(phases)]
+        pub struct PhasePorts {
+            u: TI13 In 3 3 [l1, l2, l3],
+            p: TI13 Out 2 2 [active, reactive] ?,
+            k: TI13 In 3 3 [a, b, c] ? = 1.0,
+        }
+    }
+    params! {
+        #[block_names(phases)]
+        pub struct PhaseParams {
+            enabled: TI1 In 1 1 ? = true,
+        }
+    }
+
+    fn measurement(value: f32) -> M_ME_NE_1 {
+        M_ME_NE_1 {
+            value,
+            ..Default::default()
+        }
+    }
+
+    fn parse(x: &[SmallIE], y: &[SmallIE]) -> Result<Option<usize>, ParseError> {
+        let mut shared = Shared::new();
+        write_bindings(&mut shared.latched_ports, UnboundPorts::report(), &[x, y]).unwrap();
+        let ports = UnboundPorts::parse_fallible(&mut shared.latched_ports[..])?;
+        Ok(ports.y.map(<[_]>::len))
+    }
+
+    #[test]
+    fn zero_length_optional_is_unbound() {
+        let x = SmallIE::from(M_ME_NE_1::default());
+        assert_eq!(parse(&[x], &[]), Ok(None));
+        assert_eq!(parse(&[x], &[x, x]), Ok(Some(2)));
+        assert_eq!(
+            parse(&[], &[x]).err(),
+            Some(ParseError::NotEnoughElements { binding: 0 })
+        );
+        assert_eq!(
+            parse(&[x], &[x; 3]).err(),
+            Some(ParseError::TooManyElements { binding: 1 })
+        );
+    }
+
+    fn copy(src: impl Into<SmallIE>) -> Result<Option<SmallIE>, ParseError> {
+        let mut shared = Shared::new();
+        let elements: [&[SmallIE]; 2] = [&[src.into()], &[SmallIE::default()]];
+        write_bindings(&mut shared.latched_ports, AnyPorts::report(), &elements).unwrap();
+        let ports = AnyPorts::parse_fallible(&mut shared.latched_ports[..])?;
+        *ports.dst = *ports.src;
+        Ok(SmallIE::try_from(*ports.dst).ok())
+    }
+
+    #[test]
+    fn any_ports() {
+        let on = SmallIE::from(M_SP_NA_1::default());
+        assert_eq!(copy(measurement(2.)), Ok(Some(measurement(2.).into())));
+        assert_eq!(copy(on), Ok(Some(on)));
+        assert_eq!(
+            copy(M_DP_NA_1::default()).err(),
+            Some(ParseError::InvalidElements { binding: 0 })
+        );
+    }
+
+    #[test]
+    fn unbound_param_has_default() {
+        let mut shared = Shared::new();
+        write_bindings(&mut shared.latched_params, PhaseParams::report(), &[&[]]).unwrap();
+        let params = PhaseParams::parse_fallible(&mut shared.latched_params[..]).unwrap();
+        assert!(params.enabled.value.spi());
+        assert_eq!(PhaseParams::report()[0].flags & REQUIRED, 0);
+    }
+
+    #[test]
+    fn named_elements() {
+        let u = [measurement(1.), measurement(2.), measurement(3.)];
+        let mut p = [M_ME_NE_1::default(); 2];
+        let mut ports = PhasePorts {
+            u: &u,
+            p: Some(&mut p),
+            k: &u,
+            _marker: core::marker::PhantomData,
+        };
+        assert_eq!({ ports.u_elements().l2.value }, 2.);
+        ports.p_elements_mut().unwrap().reactive.value = 5.;
+        assert_eq!({ p[1].value }, 5.);
+    }
+
+    #[test]
+    fn default_elements() {
+        let u = [SmallIE::from(measurement(2.)); 3];
+        let mut data = [0; BINDINGS_BYTES_CAP];
+        write_bindings(&mut data, PhasePorts::report(), &[&u, &[], &[]]).unwrap();
+        let ports = PhasePorts::parse_fallible(&mut data[..]).unwrap();
+        assert_eq!(ports.k.len(), 3);
+        assert_eq!({ ports.k_elements().c.value }, 1.);
+    }
+}
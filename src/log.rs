@@ -1,11 +1,54 @@
 #[cfg(not(feature = "micrortu_sdk_internal"))]
 use {core::convert::Infallible, ufmt::uWrite};
 
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
 #[cfg(not(feature = "micrortu_sdk_internal"))]
 pub struct LogWriter;
 
+/// Most verbose level compiled in, `0` being off and `5` trace.
+///
+/// Set with `max_level_*` features, or `release_max_level_*` for builds
+/// without debug assertions. Messages above it are removed at compile time.
+pub const STATIC_MAX_LEVEL: i64 = if cfg!(debug_assertions) {
+    MAX_LEVEL
+} else {
+    RELEASE_MAX_LEVEL
+};
+
+const MAX_LEVEL: i64 = if cfg!(feature = "max_level_off") {
+    0
+} else if cfg!(feature = "max_level_error") {
+    1
+} else if cfg!(feature = "max_level_warn") {
+    2
+} else if cfg!(feature = "max_level_info") {
+    3
+} else if cfg!(feature = "max_level_debug") {
+    4
+} else {
+    5
+};
+
+const RELEASE_MAX_LEVEL: i64 = if cfg!(feature = "release_max_level_off") {
+    0
+} else if cfg!(feature = "release_max_level_error") {
+    1
+} else if cfg!(feature = "release_max_level_warn") {
+    2
+} else if cfg!(feature = "release_max_level_info") {
+    3
+} else if cfg!(feature = "release_max_level_debug") {
+    4
+} else if cfg!(feature = "release_max_level_trace") {
+    5
+} else {
+    MAX_LEVEL
+};
+
 #[rustfmt::skip]
 #[cfg(not(feature = "micrortu_sdk_internal"))]
+#[cfg(not(all(any(test, feature = "testing"), not(target_arch = "wasm32"))))]
 #[allow(clippy::too_many_arguments)]
 fn log_append(len: u64, b1: u64, b2: u64, b3: u64, b4: u64, b5: u64,
     b6: u64, b7: u64, b8: u64, b9: u64, b10: u64, b11: u64,
@@ -21,6 +64,7 @@ fn log_append(len: u64, b1: u64, b2: u64, b3: u64, b4: u64, b5: u64,
 }
 
 #[cfg(not(feature = "micrortu_sdk_internal"))]
+#[cfg(not(all(any(test, feature = "testing"), not(target_arch = "wasm32"))))]
 pub fn log_emit(level: i64) {
     extern "C" {
         fn log_emit(level: i64);
@@ -28,6 +72,63 @@ pub fn log_emit(level: i64) {
     unsafe { log_emit(level) }
 }
 
+/// Native sink for `log_append`/`log_emit`, so blocks can be run on the host.
+///
+/// Emitted messages are forwarded to the `log` crate and kept per thread
+/// until collected with [`take_logs`].
+#[cfg(not(feature = "micrortu_sdk_internal"))]
+#[cfg(all(any(test, feature = "testing"), not(target_arch = "wasm32")))]
+mod host {
+    use core::cell::RefCell;
+    use std::{string::String, vec::Vec};
+
+    std::thread_local! {
+        static PENDING: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+        static EMITTED: RefCell<Vec<(i64, String)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    #[rustfmt::skip]
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn log_append(len: u64, b1: u64, b2: u64, b3: u64, b4: u64, b5: u64,
+        b6: u64, b7: u64, b8: u64, b9: u64, b10: u64, b11: u64,
+        b12: u64, b13: u64, b14: u64, b15: u64) {
+        let words = [b1, b2, b3, b4, b5, b6, b7, b8, b9, b10, b11, b12, b13, b14, b15];
+        let mut bytes = [0; 120];
+        for (chunk, word) in bytes.chunks_exact_mut(8).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        let len = (len as usize).min(bytes.len());
+        PENDING.with_borrow_mut(|pending| pending.extend_from_slice(&bytes[..len]));
+    }
+
+    pub fn log_emit(level: i64) {
+        let bytes = PENDING.with_borrow_mut(core::mem::take);
+        let message = String::from_utf8_lossy(&bytes).into_owned();
+        let log_level = match level {
+            1 => ::log::Level::Error,
+            2 => ::log::Level::Warn,
+            3 => ::log::Level::Info,
+            4 => ::log::Level::Debug,
+            _ => ::log::Level::Trace,
+        };
+        ::log::log!(log_level, "{message}");
+        EMITTED.with_borrow_mut(|emitted| emitted.push((level, message)));
+    }
+
+    /// Returns messages emitted on the current thread since the last call,
+    /// together with their levels.
+    pub fn take_logs() -> Vec<(i64, String)> {
+        EMITTED.with_borrow_mut(core::mem::take)
+    }
+}
+
+#[cfg(not(feature = "micrortu_sdk_internal"))]
+#[cfg(all(any(test, feature = "testing"), not(target_arch = "wasm32")))]
+use host::log_append;
+#[cfg(not(feature = "micrortu_sdk_internal"))]
+#[cfg(all(any(test, feature = "testing"), not(target_arch = "wasm32")))]
+pub use host::{log_emit, take_logs};
+
 #[cfg(not(feature = "micrortu_sdk_internal"))]
 impl uWrite for LogWriter {
     type Error = Infallible;
@@ -66,12 +167,66 @@ impl uWrite for LogWriter {
     }
 }
 
+/// Same as `uWrite`, for `core::fmt` formatting.
+#[cfg(not(feature = "micrortu_sdk_internal"))]
+impl core::fmt::Write for LogWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        _ = uWrite::write_str(self, s);
+        Ok(())
+    }
+}
+
+/// `log` crate facade, writing records with `log_append`/`log_emit`.
+///
+/// Installed by [`crate::init_logger`], so crates using `log::info!` and
+/// friends are visible in the firmware log.
+#[cfg(not(feature = "micrortu_sdk_internal"))]
+pub struct Logger;
+
+#[cfg(not(feature = "micrortu_sdk_internal"))]
+impl ::log::Log for Logger {
+    fn enabled(&self, metadata: &::log::Metadata<'_>) -> bool {
+        metadata.level() <= ::log::max_level()
+    }
+
+    fn log(&self, record: &::log::Record<'_>) {
+        use core::fmt::Write;
+
+        if record.level() as i64 > STATIC_MAX_LEVEL {
+            return;
+        }
+        _ = write!(LogWriter, "{}", record.args());
+        // `log::Level` discriminants match `log_emit` levels.
+        log_emit(record.level() as i64);
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs [`Logger`] as the `log` crate logger, with `level` as max level.
+///
+/// Only on wasm, native builds keep the logger of the host application.
+#[cfg(not(feature = "micrortu_sdk_internal"))]
+pub fn init(level: ::log::LevelFilter) {
+    #[cfg(target_arch = "wasm32")]
+    {
+        static LOGGER: Logger = Logger;
+        _ = ::log::set_logger(&LOGGER);
+        ::log::set_max_level(level);
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    let _ = level;
+}
+
 #[cfg(not(feature = "micrortu_sdk_internal"))]
 #[macro_export]
 macro_rules! log {
     ($level:expr, $($arg:tt)*) => {{
-        _ = ::micrortu_sdk::ufmt::uwrite!(::micrortu_sdk::log::LogWriter, $($arg)*);
-        ::micrortu_sdk::log::log_emit($level);
+        let level: i64 = $level;
+        if level <= ::micrortu_sdk::log::STATIC_MAX_LEVEL {
+            _ = ::micrortu_sdk::ufmt::uwrite!(::micrortu_sdk::log::LogWriter, $($arg)*);
+            ::micrortu_sdk::log::log_emit(level);
+        }
     }}
 }
 
@@ -120,3 +275,93 @@ macro_rules! trace {
         ::micrortu_sdk::log!(5, $($arg)*)
     }
 }
+
+/// Counts calls of a rate-limited log site, returns `true` every `n`-th call,
+/// starting with the first one.
+#[doc(hidden)]
+pub fn every(counter: &AtomicU32, n: u32) -> bool {
+    let count = counter.load(Ordering::Relaxed);
+    let next = if count + 1 >= n { 0 } else { count + 1 };
+    counter.store(next, Ordering::Relaxed);
+    count == 0
+}
+
+/// Returns `true` when `condition` becomes true, remembering it in `latch`.
+#[doc(hidden)]
+pub fn rising(latch: &AtomicBool, condition: bool) -> bool {
+    !latch.swap(condition, Ordering::Relaxed) && condition
+}
+
+/// Logs on the first call and then on every `n`-th call of that log site,
+/// at `level` from 1 (error) to 5 (trace), like [`log!`](crate::log!).
+///
+/// Calls are counted per call site, not per block instance.
+/// ```ignore
+/// micrortu_sdk::log_every!(100, 2, "input is out of range: {}", value);
+/// ```
+#[macro_export]
+macro_rules! log_every {
+    ($n:expr, $level:expr, $($arg:tt)*) => {{
+        static COUNTER: ::core::sync::atomic::AtomicU32 = ::core::sync::atomic::AtomicU32::new(0);
+        if ::micrortu_sdk::log::every(&COUNTER, $n) {
+            ::micrortu_sdk::log!($level, $($arg)*);
+        }
+    }}
+}
+
+/// Logs once when `condition` becomes true, and again only after it was false.
+/// `level` is the same as in [`log_every!`](crate::log_every!).
+///
+/// State is kept per call site, not per block instance.
+/// ```ignore
+/// micrortu_sdk::log_while!(value.is_nan(), 2, "input is NaN");
+/// ```
+#[macro_export]
+macro_rules! log_while {
+    ($condition:expr, $level:expr, $($arg:tt)*) => {{
+        static LATCH: ::core::sync::atomic::AtomicBool = ::core::sync::atomic::AtomicBool::new(false);
+        if ::micrortu_sdk::log::rising(&LATCH, $condition) {
+            ::micrortu_sdk::log!($level, $($arg)*);
+        }
+    }}
+}
+
+#[cfg(all(test, not(feature = "micrortu_sdk_internal")))]
+mod test {
+    use ::log::Log;
+
+    use super::{take_logs, Logger};
+
+    #[test]
+    fn rate_limited() {
+        for step in 0..7 {
+            crate::log_every!(3, 2, "step {}", step);
+        }
+        for condition in [false, true, true, false, true] {
+            crate::log_while!(condition, 1, "condition is set");
+        }
+        let logs: Vec<_> = take_logs().into_iter().map(|(_, msg)| msg).collect();
+        assert_eq!(
+            logs,
+            [
+                "step 0",
+                "step 3",
+                "step 6",
+                "condition is set",
+                "condition is set"
+            ]
+        );
+    }
+
+    #[test]
+    fn facade_emits_records() {
+        let long = "x".repeat(200);
+        Logger.log(
+            &::log::Record::builder()
+                .level(::log::Level::Error)
+                .args(format_args!("value is {}, {long}", 42))
+                .build(),
+        );
+        assert_eq!(take_logs(), [(1, format!("value is 42, {long}"))]);
+    }
+}
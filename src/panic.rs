@@ -0,0 +1,99 @@
+/*!
+Opt-in panic handler for wasm blocks.
+
+With the `panic-handler` feature, `micrortu_sdk` provides `#[panic_handler]`
+that logs the panic location and message at error level, prefixed with the
+name of the block being executed, and then traps. Without it, panics on wasm
+only show up as a trap in the firmware log.
+
+Don't enable it if your crate or other dependency already defines a panic
+handler.
+*/
+
+#[cfg(feature = "panic-handler")]
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+#[cfg(feature = "panic-handler")]
+static CURRENT_BLOCK: AtomicPtr<&'static str> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Records the block whose export is being executed.
+/// Called by exports generated with `register_block!`.
+#[doc(hidden)]
+#[inline(always)]
+pub fn enter_block(name: &'static &'static str) {
+    #[cfg(feature = "panic-handler")]
+    CURRENT_BLOCK.store(core::ptr::from_ref(name).cast_mut(), Ordering::Relaxed);
+    #[cfg(not(feature = "panic-handler"))]
+    let _ = name;
+}
+
+/// Name of the block whose export is being executed, if tracked.
+#[must_use]
+pub fn current_block() -> Option<&'static str> {
+    #[cfg(feature = "panic-handler")]
+    {
+        // SAFETY: only `&'static &'static str` are ever stored.
+        unsafe { CURRENT_BLOCK.load(Ordering::Relaxed).as_ref().copied() }
+    }
+    #[cfg(not(feature = "panic-handler"))]
+    None
+}
+
+#[cfg(all(
+    feature = "panic-handler",
+    target_arch = "wasm32",
+    not(feature = "micrortu_sdk_internal")
+))]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo<'_>) -> ! {
+    use core::fmt::Write;
+
+    use crate::log::{log_emit, LogWriter};
+
+    let mut writer = LogWriter;
+    if let Some(block) = current_block() {
+        _ = write!(writer, "Block `{block}` panicked");
+    } else {
+        _ = write!(writer, "Panicked");
+    }
+    if let Some(location) = info.location() {
+        _ = write!(
+            writer,
+            " at {}:{}:{}",
+            location.file(),
+            location.line(),
+            location.column()
+        );
+    }
+    _ = write!(writer, ": {}", info.message());
+    log_emit(1);
+
+    core::arch::wasm32::unreachable()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ports, BlockPorts};
+
+    ports! {
+        #[block_names(panicking)]
+        pub struct Ports {
+            x: TI13 In 1 1,
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "panic-handler")]
+    fn tracks_current_block() {
+        use super::{current_block, enter_block};
+
+        enter_block(&"counter");
+        assert_eq!(current_block(), Some("counter"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to parse bindings: BadHeader")]
+    fn parse_failure_panics() {
+        Ports::parse(&mut []);
+    }
+}
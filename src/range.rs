@@ -0,0 +1,103 @@
+/*!
+Checking measured values against engineering ranges.
+
+Ports with `#[range(min, max)]` of measured value types get a generated
+`<port>_checked()` accessor, which returns values with IV and OV quality
+bits set when they are outside of the range.
+
+# Example
+
+```rust
+use micrortu_sdk::{ie_base::{M_ME_NE_1, QualityDescriptor}, range::RangeCheck};
+
+let value = M_ME_NE_1 { value: 120., ..Default::default() };
+let qds = value.checked(0., 100.).qds;
+assert!(qds.iv() && qds.ov());
+```
+*/
+
+use ie_base::{QualityDescriptor, M_ME_NB_1, M_ME_NE_1, TI136, TI137, TI138, TI139};
+
+/// Measured value with quality descriptor.
+pub trait RangeCheck: Copy {
+    /// Returns the value with IV and OV set if it is outside of `min..=max`.
+    #[must_use]
+    fn checked(self, min: f64, max: f64) -> Self;
+}
+
+macro_rules! impl_range_check {
+    ($($T:ty),*) => {$(
+        impl RangeCheck for $T {
+            #[allow(clippy::cast_precision_loss, clippy::cast_lossless)]
+            fn checked(mut self, min: f64, max: f64) -> Self {
+                let value = self.value as f64;
+                if !(min..=max).contains(&value) {
+                    let mut qds = self.qds;
+                    qds.set_iv(true);
+                    qds.set_ov(true);
+                    self.qds = qds;
+                }
+                self
+            }
+        }
+    )*};
+}
+
+impl_range_check!(M_ME_NB_1, M_ME_NE_1, TI136, TI137, TI138, TI139);
+
+#[cfg(test)]
+mod test {
+    use ie_base::{QualityDescriptor, M_ME_NE_1, TI137};
+
+    use crate::ports;
+
+    ports! {
+        #[block_names(ranged)]
+        pub struct Ports {
+            #[unit = "kW"]
+            #[range(0, 100)]
+            power: TI13 In 1 1,
+            #[range(-10, 10)]
+            offset: TI137 In 1 1 ?,
+            #[range(0.5, 1.5)]
+            samples: TI13 In 1 3,
+            #[range(0, 1)]
+            extra: TI13 InOut 1 2 ?,
+            #[labels(off = "Closed", on = "Open")]
+            breaker: TI1 In 1 1,
+        }
+    }
+
+    fn measurement(value: f32) -> M_ME_NE_1 {
+        M_ME_NE_1 {
+            value,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn checked_accessors() {
+        let power = measurement(120.);
+        let offset = TI137 {
+            value: -5,
+            ..Default::default()
+        };
+        let samples = [measurement(1.), measurement(f32::NAN)];
+        let ports = Ports {
+            power: &power,
+            offset: Some(&offset),
+            samples: &samples,
+            extra: None,
+            breaker: &Default::default(),
+            _marker: core::marker::PhantomData,
+        };
+
+        let qds = ports.power_checked().qds;
+        assert!(qds.iv() && qds.ov());
+        assert!(ports.power.qds.is_good());
+        assert!(ports.offset_checked().unwrap().qds.is_good());
+        let samples: Vec<bool> = ports.samples_checked().map(|s| s.qds.iv()).collect();
+        assert_eq!(samples, [false, true]);
+        assert_eq!(ports.extra_checked().count(), 0);
+    }
+}
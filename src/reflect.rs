@@ -0,0 +1,72 @@
+/*!
+Access to bindings of generated `ports!`/`params!` structs without knowing
+their concrete type, e.g. for diagnostics that dump all inputs on error.
+
+# Example
+
+```ignore
+fn dump<R: Reflect>(bindings: &R) {
+    for view in bindings.bindings() {
+        micrortu_sdk::info!("{}: {} elements", view.name, view.len());
+    }
+}
+```
+*/
+
+use crate::{BindingView, NativeBindingDefinition};
+
+/// Implemented by `ports!` and `params!` alongside `BlockPorts`.
+pub trait Reflect {
+    /// Definitions of bindings, in declaration order.
+    const DEFINITIONS: &'static [NativeBindingDefinition<'static>];
+
+    /// Elements of binding at `index` of [`Self::DEFINITIONS`].
+    /// Unbound optional bindings have no elements.
+    fn binding(&self, index: usize) -> Option<BindingView<'_>>;
+
+    /// Elements of all bindings, in declaration order.
+    fn bindings(&self) -> impl Iterator<Item = BindingView<'_>> {
+        (0..Self::DEFINITIONS.len()).filter_map(move |index| self.binding(index))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ie_base::{SmallIE, M_ME_NE_1, M_SP_NA_1};
+
+    use super::Reflect;
+    use crate::{ports, Direction, IN, OUT};
+
+    ports! {
+        #[block_names(reflected)]
+        pub struct Ports {
+            x: TI13 In 1 1,
+            flags: TI1 In 1 2 ?,
+            y: TI13 Out 2 2,
+        }
+    }
+
+    #[test]
+    fn bindings() {
+        let x = M_ME_NE_1 {
+            value: 1.,
+            ..Default::default()
+        };
+        let mut y = [M_ME_NE_1::default(); 2];
+        let ports = Ports {
+            x: &x,
+            flags: None,
+            y: &mut y,
+            _marker: core::marker::PhantomData,
+        };
+
+        let views: Vec<(&str, Direction, usize)> = ports
+            .bindings()
+            .map(|view| (view.name, view.direction, view.len()))
+            .collect();
+        assert_eq!(views, [("x", IN, 1), ("flags", IN, 0), ("y", OUT, 2)]);
+        assert_eq!(ports.binding(0).unwrap().get(0), Some(SmallIE::from(x)));
+        assert!(ports.binding(1).unwrap().as_slice::<M_SP_NA_1>().is_some());
+        assert!(ports.binding(3).is_none());
+    }
+}
@@ -0,0 +1,593 @@
+/*!
+Native test harness for blocks registered with `register_block!`.
+
+Drives a block through `factory`, `init`, `step` and its lifecycle hooks
+without a wasm runtime.
+Ports and params are addressed by name, using the binding definitions
+generated by `ports!`/`params!`, and their values are encoded into [`Shared`]
+the same way `MicroRTU` firmware does it.
+
+Available on native targets with the `testing` feature.
+
+# Example
+
+```ignore
+use micrortu_sdk::ie_base::{M_ME_NE_1, SmallIE};
+use micrortu_sdk::testing::{factory_input, BlockHarness};
+
+let mut harness = BlockHarness::new(BLOCK_counter, &factory_input(100)).unwrap();
+harness.set_port("count", [M_ME_NE_1::default()]).unwrap();
+assert_eq!(harness.step().unwrap(), 0);
+assert_eq!(harness.port("count").unwrap()[0].extract_ti::<M_ME_NE_1>().unwrap().value, 1.);
+```
+*/
+
+use core::fmt;
+use std::{boxed::Box, string::String, vec, vec::Vec};
+
+use ie_base::SmallIE;
+
+use crate::{
+    BindingsReader, BindingsWriter, Config, Direction, EncodeError, FactoryInput,
+    NativeBindingDefinition, RegisteredBlock, Shared, StepResult, ANY, BINDINGS_BYTES_CAP,
+};
+
+/// Errors reported by [`BlockHarness`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HarnessError {
+    /// Block factory returned `None`.
+    FactoryFailed,
+    /// Config does not fit into `FactoryInput::config`.
+    ConfigTooLarge { len: usize },
+    /// Block has no port or param with that name.
+    UnknownBinding(String),
+    /// Value type doesn't match the type declared for the binding.
+    TypeMismatch {
+        binding: String,
+        expected: u8,
+        got: u8,
+    },
+    /// Bindings can't be encoded into `Shared`.
+    Encode(EncodeError),
+    /// Binding definition or data written by the block is malformed.
+    InvalidData(String),
+    /// Block doesn't export that hook.
+    MissingHook(&'static str),
+    /// Block left element `index` of `ANY` output `binding` unset.
+    Unset { binding: String, index: usize },
+}
+
+/// Creates `FactoryInput` without config.
+#[must_use]
+pub fn factory_input(control_period_ms: u32) -> FactoryInput {
+    FactoryInput {
+        control_period_ms,
+        config_len: 0,
+        config: crate::wasm_global_shared_data::Config([0; 504]),
+    }
+}
+
+/// Creates `FactoryInput` with `config` serialized the way firmware passes it.
+pub fn factory_input_with_config<C: Config>(
+    control_period_ms: u32,
+    mut config: C,
+) -> Result<FactoryInput, HarnessError> {
+    let bytes = config.as_mut_bytes();
+    let mut input = factory_input(control_period_ms);
+    let target = input
+        .config
+        .0
+        .get_mut(..bytes.len())
+        .ok_or(HarnessError::ConfigTooLarge { len: bytes.len() })?;
+    target.copy_from_slice(bytes);
+    input.config_len = bytes.len() as u32;
+    Ok(input)
+}
+
+/// Single block instance, driven natively.
+///
+/// Dropping the harness calls block's `deinit` hook, releasing pooled instances.
+pub struct BlockHarness<B: 'static> {
+    block: RegisteredBlock<B>,
+    instance: &'static mut B,
+    shared: Box<Shared>,
+    /// `None` for elements of `ANY` outputs the block didn't write.
+    ports: Vec<Vec<Option<SmallIE>>>,
+    params: Vec<Vec<Option<SmallIE>>>,
+}
+
+impl<B> BlockHarness<B> {
+    /// Calls block's factory with `input`. All bindings start unbound.
+    pub fn new(block: RegisteredBlock<B>, input: &FactoryInput) -> Result<Self, HarnessError> {
+        let instance = (block.factory)(input).ok_or(HarnessError::FactoryFailed)?;
+        Ok(Self {
+            block,
+            instance,
+            shared: Box::default(),
+            ports: vec![Vec::new(); block.ports.len()],
+            params: vec![Vec::new(); block.params.len()],
+        })
+    }
+
+    /// Binds port `name` to `values`.
+    ///
+    /// For `Out` ports only the number of values matters, the firmware
+    /// always passes them zeroed.
+    pub fn set_port<T: Into<SmallIE>>(
+        &mut self,
+        name: &str,
+        values: impl IntoIterator<Item = T>,
+    ) -> Result<&mut Self, HarnessError> {
+        let idx = find(self.block.ports, name)?;
+        self.ports[idx] = typed(&self.block.ports[idx], values)?;
+        Ok(self)
+    }
+
+    /// Binds param `name` to `values`.
+    pub fn set_param<T: Into<SmallIE>>(
+        &mut self,
+        name: &str,
+        values: impl IntoIterator<Item = T>,
+    ) -> Result<&mut Self, HarnessError> {
+        let idx = find(self.block.params, name)?;
+        self.params[idx] = typed(&self.block.params[idx], values)?;
+        Ok(self)
+    }
+
+    /// Current values of port `name`. Updated after each `init` and `step`.
+    ///
+    /// Fails with [`HarnessError::Unset`] if the block left an element of an
+    /// `ANY` output unset.
+    pub fn port(&self, name: &str) -> Result<Vec<SmallIE>, HarnessError> {
+        values(self.block.ports, &self.ports, name)
+    }
+
+    /// Current values of param `name`. Updated after each `init` and `step`.
+    pub fn param(&self, name: &str) -> Result<Vec<SmallIE>, HarnessError> {
+        values(self.block.params, &self.params, name)
+    }
+
+    /// Values of `Out` and `InOut` ports, in declaration order. `None` for
+    /// elements of `ANY` outputs the block left unset.
+    pub fn outputs(&self) -> impl Iterator<Item = (&'static str, &[Option<SmallIE>])> {
+        self.block
+            .ports
+            .iter()
+            .zip(&self.ports)
+            .filter(|(def, _)| def.direction != Direction::IN)
+            .map(|(def, values)| (def.name, &values[..]))
+    }
+
+    /// Encodes bindings, calls block's `init` and reads back outputs.
+    pub fn init(&mut self) -> Result<StepResult, HarnessError> {
+        self.run(self.block.init)
+    }
+
+    /// Encodes bindings, calls block's `step` and reads back outputs.
+    pub fn step(&mut self) -> Result<StepResult, HarnessError> {
+        self.run(self.block.step)
+    }
+
+    /// Encodes bindings, calls block's `reset` hook and reads back outputs.
+    pub fn reset(&mut self) -> Result<StepResult, HarnessError> {
+        let reset = self.block.reset.ok_or(HarnessError::MissingHook("reset"))?;
+        self.run(reset)
+    }
+
+    /// Calls block's `reconfigure` hook with new `input`.
+    pub fn reconfigure(&mut self, input: &FactoryInput) -> Result<StepResult, HarnessError> {
+        let reconfigure = self
+            .block
+            .reconfigure
+            .ok_or(HarnessError::MissingHook("reconfigure"))?;
+        Ok(reconfigure(input, self.instance))
+    }
+
+    /// Drops the harness, which calls block's `deinit` hook, if any.
+    pub fn deinit(self) {
+        drop(self);
+    }
+
+    /// Block instance created by the factory.
+    pub fn block(&mut self) -> &mut B {
+        self.instance
+    }
+
+    /// Raw data as seen by the block on the last call.
+    #[must_use]
+    pub fn shared(&self) -> &Shared {
+        &self.shared
+    }
+
+    fn run(
+        &mut self,
+        f: fn(&mut Shared, &mut B) -> StepResult,
+    ) -> Result<StepResult, HarnessError> {
+        encode(
+            self.block.ports,
+            &self.ports,
+            &mut self.shared.latched_ports,
+        )?;
+        encode(
+            self.block.params,
+            &self.params,
+            &mut self.shared.latched_params,
+        )?;
+        let result = f(&mut self.shared, self.instance);
+        decode(
+            self.block.ports,
+            &mut self.ports,
+            &self.shared.latched_ports,
+        )?;
+        decode(
+            self.block.params,
+            &mut self.params,
+            &self.shared.latched_params,
+        )?;
+        Ok(result)
+    }
+}
+
+impl<B> Drop for BlockHarness<B> {
+    fn drop(&mut self) {
+        if let Some(deinit) = self.block.deinit {
+            deinit(self.instance);
+        }
+    }
+}
+
+fn find(defs: &[NativeBindingDefinition<'_>], name: &str) -> Result<usize, HarnessError> {
+    defs.iter()
+        .position(|def| def.name == name)
+        .ok_or_else(|| HarnessError::UnknownBinding(name.into()))
+}
+
+fn values(
+    defs: &[NativeBindingDefinition<'_>],
+    values: &[Vec<Option<SmallIE>>],
+    name: &str,
+) -> Result<Vec<SmallIE>, HarnessError> {
+    let idx = find(defs, name)?;
+    unset(&defs[idx], &values[idx])
+}
+
+/// Values of binding `def`, or error for the first unset one.
+fn unset(
+    def: &NativeBindingDefinition<'_>,
+    values: &[Option<SmallIE>],
+) -> Result<Vec<SmallIE>, HarnessError> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            value.ok_or_else(|| HarnessError::Unset {
+                binding: def.name.into(),
+                index,
+            })
+        })
+        .collect()
+}
+
+fn typed<T: Into<SmallIE>>(
+    def: &NativeBindingDefinition<'_>,
+    values: impl IntoIterator<Item = T>,
+) -> Result<Vec<Option<SmallIE>>, HarnessError> {
+    values
+        .into_iter()
+        .map(|value| {
+            let value = value.into();
+            if def.typ == ANY || value.typecode() == def.typ {
+                Ok(Some(value))
+            } else {
+                Err(HarnessError::TypeMismatch {
+                    binding: def.name.into(),
+                    expected: def.typ,
+                    got: value.typecode(),
+                })
+            }
+        })
+        .collect()
+}
+
+fn encode(
+    defs: &[NativeBindingDefinition<'_>],
+    values: &[Vec<Option<SmallIE>>],
+    target: &mut [u8; BINDINGS_BYTES_CAP],
+) -> Result<(), HarnessError> {
+    let mut writer = BindingsWriter::new(target, defs.len())?;
+    for (def, values) in defs.iter().zip(values) {
+        // Payloads of `Out` bindings are zeroed, only the number of elements matters.
+        let values = if def.direction == Direction::OUT {
+            values
+                .iter()
+                .map(|value| value.unwrap_or_default())
+                .collect()
+        } else {
+            unset(def, values)?
+        };
+        writer.push(def, &values)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Reads back values of `Out` and `InOut` bindings written by the block.
+/// Elements that don't decode stay in place as `None`, keeping the length.
+fn decode(
+    defs: &[NativeBindingDefinition<'_>],
+    values: &mut [Vec<Option<SmallIE>>],
+    source: &[u8],
+) -> Result<(), HarnessError> {
+    let views = BindingsReader::new(source, defs);
+    for ((def, values), view) in defs.iter().zip(values).zip(views) {
+        let view = view.map_err(|_| HarnessError::InvalidData(def.name.into()))?;
+        if view.is_output() {
+            *values = (0..view.len()).map(|index| view.get(index)).collect();
+        }
+    }
+
+    Ok(())
+}
+
+impl fmt::Display for HarnessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FactoryFailed => write!(f, "block factory returned None"),
+            Self::ConfigTooLarge { len } => write!(f, "config of {len} bytes is too large"),
+            Self::UnknownBinding(name) => write!(f, "unknown binding `{name}`"),
+            Self::TypeMismatch {
+                binding,
+                expected,
+                got,
+            } => write!(f, "binding `{binding}` expects TI{expected}, got TI{got}"),
+            Self::Encode(err) => write!(f, "failed to encode bindings: {err:?}"),
+            Self::InvalidData(name) => write!(f, "invalid data for binding `{name}`"),
+            Self::MissingHook(name) => write!(f, "block has no `{name}` hook"),
+            Self::Unset { binding, index } => {
+                write!(f, "block left element {index} of `{binding}` unset")
+            }
+        }
+    }
+}
+
+impl core::error::Error for HarnessError {}
+
+impl From<EncodeError> for HarnessError {
+    fn from(err: EncodeError) -> Self {
+        Self::Encode(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::boxed::Box;
+
+    use ie_base::{SmallIE, M_DP_NA_1, M_ME_NE_1, M_SP_NA_1};
+
+    use super::{factory_input, factory_input_with_config, BlockHarness, HarnessError};
+    use crate::{
+        params, ports, register_block, BlockPorts, EncodeError, FactoryInput, ParseError, Shared,
+        StepResult,
+    };
+
+    pub struct Scale {
+        gain: f32,
+        steps: u32,
+    }
+
+    #[derive(zerocopy::IntoBytes, zerocopy::FromBytes, zerocopy::Immutable, crate::Config)]
+    #[block_names(harness_scale)]
+    #[repr(C)]
+    pub struct ScaleConfig {
+        /// Multiplier applied to `x`.
+        gain: f32,
+    }
+
+    ports! {
+        #[block_names(harness_scale)]
+        pub struct Ports {
+            /// Scaled value.
+            x: TI13 In 1 1,
+            #[description = "Added after scaling"]
+            bias: TI13 In 1 1 ?,
+            y: TI13 Out 1 3,
+            count: TI13 InOut 1 1,
+        }
+    }
+    params! {
+        #[block_names(harness_scale)]
+        pub struct Params {
+            enabled: TI1 In 1 1 ? = true,
+        }
+    }
+
+    fn gain(input: &FactoryInput) -> Option<f32> {
+        if input.config_len == 0 {
+            return Some(1.);
+        }
+        Some(f32::from_le_bytes(input.config.0[..4].try_into().ok()?))
+    }
+
+    pub fn factory(input: &FactoryInput) -> Option<&'static mut Scale> {
+        let gain = gain(input)?;
+        Some(Box::leak(Box::new(Scale { gain, steps: 0 })))
+    }
+
+    pub fn init(_: &mut Shared, _: &mut Scale) -> StepResult {
+        0
+    }
+
+    pub fn step(shared: &mut Shared, block: &mut Scale) -> StepResult {
+        let ports = Ports::parse(&mut shared.latched_ports[..]);
+        let params = Params::parse(&mut shared.latched_params[..]);
+        if !params.enabled.value.spi() {
+            return 1;
+        }
+        let bias = ports.bias.map_or(0., |b| b.value);
+        for y in ports.y.iter_mut() {
+            y.value = ports.x.value * block.gain + bias;
+        }
+        ports.count.value += 1.;
+        block.steps += 1;
+        0
+    }
+
+    register_block!(
+        /// Scales `x` by configured gain.
+        Scale,
+        harness_scale,
+        factory,
+        init,
+        step,
+    );
+
+    pub struct Passthrough;
+
+    ports! {
+        #[block_names(harness_copy)]
+        pub struct CopyPorts {
+            src: ANY(TI1 | TI13) In 1 1,
+            dst: ANY Out 1 1,
+        }
+    }
+    params! {
+        #[block_names(harness_copy)]
+        pub struct CopyParams {}
+    }
+
+    pub fn copy_factory(_: &FactoryInput) -> Option<&'static mut Passthrough> {
+        Some(Box::leak(Box::new(Passthrough)))
+    }
+
+    pub fn copy_init(_: &mut Shared, _: &mut Passthrough) -> StepResult {
+        0
+    }
+
+    pub fn copy_step(shared: &mut Shared, _: &mut Passthrough) -> Result<(), ParseError> {
+        let ports = CopyPorts::parse_fallible(&mut shared.latched_ports[..])?;
+        *ports.dst = *ports.src;
+        Ok(())
+    }
+
+    register_block!(
+        Passthrough,
+        harness_copy,
+        copy_factory,
+        copy_init,
+        copy_step
+    );
+
+    fn value(ie: SmallIE) -> f32 {
+        ie.extract_ti::<M_ME_NE_1>().unwrap().value
+    }
+
+    fn measurement(value: f32) -> M_ME_NE_1 {
+        M_ME_NE_1 {
+            value,
+            ..Default::default()
+        }
+    }
+
+    fn enabled(on: bool) -> M_SP_NA_1 {
+        let mut ie = M_SP_NA_1::default();
+        ie.value.set_spi(on);
+        ie
+    }
+
+    #[test]
+    fn step_reads_inputs_and_writes_outputs() {
+        let input = factory_input_with_config(100, ScaleConfig { gain: 2. }).unwrap();
+        let mut harness = BlockHarness::new(BLOCK_harness_scale, &input).unwrap();
+        harness
+            .set_port("x", [measurement(3.)])
+            .unwrap()
+            .set_port("y", [M_ME_NE_1::default(); 2])
+            .unwrap()
+            .set_port("count", [measurement(0.)])
+            .unwrap()
+            .set_param("enabled", [enabled(true)])
+            .unwrap();
+
+        assert_eq!(harness.init().unwrap(), 0);
+        assert_eq!(harness.step().unwrap(), 0);
+        let y: Vec<f32> = harness
+            .port("y")
+            .unwrap()
+            .iter()
+            .map(|&ie| value(ie))
+            .collect();
+        assert_eq!(y, [6., 6.]);
+
+        harness.set_port("bias", [measurement(1.)]).unwrap();
+        assert_eq!(harness.step().unwrap(), 0);
+        assert_eq!(value(harness.port("y").unwrap()[0]), 7.);
+        assert_eq!(value(harness.port("count").unwrap()[0]), 2.);
+        assert_eq!(harness.block().steps, 2);
+        let outputs: Vec<_> = harness
+            .outputs()
+            .map(|(name, values)| (name, values.len()))
+            .collect();
+        assert_eq!(outputs, [("y", 2), ("count", 1)]);
+    }
+
+    #[test]
+    fn step_result_is_returned() {
+        let mut harness = BlockHarness::new(BLOCK_harness_scale, &factory_input(100)).unwrap();
+        harness
+            .set_port("x", [measurement(3.)])
+            .unwrap()
+            .set_port("y", [M_ME_NE_1::default()])
+            .unwrap()
+            .set_port("count", [measurement(0.)])
+            .unwrap()
+            .set_param("enabled", [enabled(false)])
+            .unwrap();
+
+        assert_eq!(harness.step().unwrap(), 1);
+        assert_eq!(value(harness.port("count").unwrap()[0]), 0.);
+    }
+
+    #[test]
+    fn bindings_are_checked() {
+        let mut harness = BlockHarness::new(BLOCK_harness_scale, &factory_input(100)).unwrap();
+        assert_eq!(
+            harness.set_port("z", [measurement(1.)]).err(),
+            Some(HarnessError::UnknownBinding("z".into()))
+        );
+        assert_eq!(
+            harness.set_port("x", [enabled(true)]).err(),
+            Some(HarnessError::TypeMismatch {
+                binding: "x".into(),
+                expected: 13,
+                got: 1
+            })
+        );
+        harness.set_port("y", [M_ME_NE_1::default(); 100]).unwrap();
+        assert_eq!(
+            harness.step().err(),
+            Some(HarnessError::Encode(EncodeError::Overflow))
+        );
+    }
+
+    #[test]
+    fn unset_any_output() {
+        let mut harness = BlockHarness::new(BLOCK_harness_copy, &factory_input(100)).unwrap();
+        harness
+            .set_port("src", [M_DP_NA_1::default()])
+            .unwrap()
+            .set_port("dst", [SmallIE::default()])
+            .unwrap();
+        assert_ne!(harness.step().unwrap(), 0);
+        assert_eq!(
+            harness.outputs().collect::<Vec<_>>(),
+            [("dst", &[None][..])]
+        );
+        assert_eq!(
+            harness.port("dst").err(),
+            Some(HarnessError::Unset {
+                binding: "dst".into(),
+                index: 0
+            })
+        );
+    }
+}
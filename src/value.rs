@@ -0,0 +1,231 @@
+/*!
+Plain values of bindings, for value views declared in `ports!`/`params!`.
+
+A binding declared as `x: TI13 as f32 In 1 1` gets an `x()` getter returning
+`f32`, and `y: TI1 as bool Out 1 1` gets a `set_y(bool)` setter. Values are
+converted like [`SmallIE::update_from`] does, so any binding type can be
+viewed as any of [`Value`] types.
+
+Quality of outputs written by setters is chosen by [`QualityPolicy`], set on
+the struct with `#[quality(propagate)]` (default), `#[quality(good)]` or
+`#[quality(keep)]` and available as its `QUALITY_POLICY` constant.
+
+# Example
+
+```rust
+use micrortu_sdk::{ie_base::{M_ME_NE_1, M_SP_NA_1}, value};
+
+let x = M_ME_NE_1 { value: 2.5, ..Default::default() };
+assert_eq!(value::read::<_, i64>(x), 2);
+
+let mut y = M_SP_NA_1::default();
+value::write(&mut y, true, None);
+assert!(y.value.spi());
+```
+*/
+
+use ie_base::{
+    generic_ie::IEMeta, QualityDescriptor, SmallIE, M_ME_NE_1, M_SP_NA_1, QDS, TI138, TI139,
+};
+
+/// How value setters set quality of outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityPolicy {
+    /// Outputs get combined quality of all inputs with value views.
+    /// A flag is set if it's set on any of the inputs.
+    Propagate,
+    /// Outputs are always written with good quality.
+    Good,
+    /// Outputs keep their quality, the block sets it explicitly.
+    Keep,
+}
+
+impl QualityPolicy {
+    /// Quality to pass to [`write`], given combined quality of `inputs`.
+    pub fn output_quality(self, inputs: impl FnOnce() -> QDS) -> Option<QDS> {
+        match self {
+            Self::Propagate => Some(inputs()),
+            Self::Good => Some(QDS::default()),
+            Self::Keep => None,
+        }
+    }
+}
+
+/// Plain value a binding can be viewed as.
+pub trait Value: Copy {
+    /// Reads a value from `ie`.
+    fn from_ie(ie: &SmallIE) -> Self;
+    /// IE of the closest type, holding `self` with good quality.
+    fn to_ie(self) -> SmallIE;
+}
+
+macro_rules! impl_value {
+    ($($T:ty => $ie:ident, |$v:ident| $from:expr, |$ie_v:ident| $to:expr;)*) => {$(
+        impl Value for $T {
+            fn from_ie(ie: &SmallIE) -> Self {
+                let mut this = SmallIE::from($ie::default());
+                this.update_from(ie);
+                let $v = this.extract_ti::<$ie>().expect("Type is not changed by update");
+                $from
+            }
+
+            fn to_ie(self) -> SmallIE {
+                let $ie_v = self;
+                SmallIE::from($to)
+            }
+        }
+    )*};
+}
+
+impl_value! {
+    f32 => M_ME_NE_1, |ie| ie.value, |value| M_ME_NE_1 { value, ..Default::default() };
+    i64 => TI139, |ie| ie.value, |value| TI139 { value, ..Default::default() };
+    u64 => TI138, |ie| ie.value, |value| TI138 { value, ..Default::default() };
+    bool => M_SP_NA_1, |ie| ie.value.spi(), |value| M_SP_NA_1::from(value);
+}
+
+/// Reads plain value of `ie`.
+pub fn read<T: IEMeta, V: Value>(ie: T) -> V {
+    V::from_ie(&ie.into())
+}
+
+/// Updates `target` with `value`. Quality descriptor of `target`, if it has
+/// one, is set to `quality`, or left unchanged if `quality` is `None`.
+pub fn write<T: IEMeta, V: Value>(target: &mut T, value: V, quality: Option<QDS>) {
+    let old: SmallIE = (*target).into();
+    let mut ie = old;
+    ie.update_from(&value.to_ie());
+    if let Some(qds) = ie.try_get_qds_mut() {
+        match (quality, old.try_get_qds()) {
+            (Some(quality), _) => qds.update_from(&quality),
+            (None, Some(old)) => qds.update_from(old),
+            (None, None) => {}
+        }
+    }
+    *target = ie.extract_ti().expect("Type is not changed by update");
+}
+
+/// Sets flags of `ie`'s quality descriptor in `quality`, for
+/// [`QualityPolicy::Propagate`].
+pub fn merge_quality<T: IEMeta>(quality: &mut QDS, ie: T) {
+    let ie: SmallIE = ie.into();
+    let Some(qds) = ie.try_get_qds() else {
+        return;
+    };
+    quality.set_ov(quality.ov() || qds.ov());
+    quality.set_bl(quality.bl() || qds.bl());
+    quality.set_sb(quality.sb() || qds.sb());
+    quality.set_nt(quality.nt() || qds.nt());
+    quality.set_iv(quality.iv() || qds.iv());
+}
+
+#[cfg(test)]
+mod test {
+    use ie_base::{QualityDescriptor, M_ME_NE_1, M_SP_NA_1, QDS, TI139};
+
+    use super::QualityPolicy;
+    use crate::ports;
+
+    ports! {
+        #[block_names(value_views)]
+        pub struct Ports {
+            x: TI13 as f32 In 1 1,
+            bias: TI139 as i64 In 1 1 ?,
+            enabled: TI1 as bool In 1 3,
+            y: TI13 as f32 Out 1 1,
+            flags: TI1 as bool Out 1 2 ?,
+            counter: TI139 as u64 InOut 1 1,
+        }
+    }
+
+    ports! {
+        #[block_names(value_views_keep)]
+        #[quality(keep)]
+        pub struct KeepPorts {
+            x: TI13 as f32 In 1 1,
+            y: TI13 as i64 Out 1 1,
+        }
+    }
+
+    #[test]
+    fn views() {
+        let x = M_ME_NE_1 {
+            value: 1.5,
+            qds: QDS::NONTOPICAL,
+        };
+        let enabled = [M_SP_NA_1::from(true), M_SP_NA_1::from(false)];
+        let mut y = M_ME_NE_1::default();
+        let mut counter = TI139 {
+            value: 2,
+            ..Default::default()
+        };
+        let mut ports = Ports {
+            x: &x,
+            bias: None,
+            enabled: &enabled,
+            y: &mut y,
+            flags: None,
+            counter: &mut counter,
+            _marker: core::marker::PhantomData,
+        };
+
+        assert_eq!(ports.x(), 1.5);
+        assert_eq!(ports.bias(), None);
+        assert_eq!(ports.enabled().collect::<Vec<_>>(), [true, false]);
+        assert_eq!(ports.counter(), 2);
+        assert!(ports.quality().nt());
+
+        ports.set_y(ports.x() * 2.);
+        ports.set_flags([true]);
+        ports.set_counter(ports.counter() + 1);
+        assert_eq!({ y.value }, 3.);
+        assert!(y.qds.nt());
+        assert_eq!({ counter.value }, 3);
+
+        let mut y = M_ME_NE_1 {
+            value: 0.,
+            qds: QDS::INVALID,
+        };
+        let mut ports = KeepPorts {
+            x: &x,
+            y: &mut y,
+            _marker: core::marker::PhantomData,
+        };
+        ports.set_y(7);
+        assert_eq!({ y.value }, 7.);
+        assert!(y.qds.iv() && !y.qds.nt());
+    }
+
+    ports! {
+        #[block_names(value_views_good)]
+        #[quality(good)]
+        pub struct GoodPorts {
+            x: TI13 as f32 In 1 1,
+            y: TI13 as f32 Out 1 1,
+        }
+    }
+
+    #[test]
+    fn policies() {
+        assert_eq!(Ports::QUALITY_POLICY, QualityPolicy::Propagate);
+        assert_eq!(KeepPorts::QUALITY_POLICY, QualityPolicy::Keep);
+        assert_eq!(GoodPorts::QUALITY_POLICY, QualityPolicy::Good);
+
+        let x = M_ME_NE_1 {
+            value: 1.,
+            qds: QDS::INVALID,
+        };
+        let mut y = M_ME_NE_1 {
+            value: 0.,
+            qds: QDS::NONTOPICAL,
+        };
+        let mut ports = GoodPorts {
+            x: &x,
+            y: &mut y,
+            _marker: core::marker::PhantomData,
+        };
+        ports.set_y(ports.x());
+        assert_eq!({ y.value }, 1.);
+        assert!(!y.qds.iv() && !y.qds.nt());
+    }
+}
@@ -0,0 +1,277 @@
+use ie_base::{generic_ie::IEMeta, IEBuf, IeType, SmallIE};
+use zerocopy::FromBytes;
+
+use crate::{Direction, NativeBindingDefinition, ParseError, ANY};
+
+/// Decoder for the layout written by `BindingsWriter`.
+///
+/// Yields a `BindingView` per binding definition, in order.
+/// Stops after the first error.
+///
+/// ```rust
+/// use micrortu_wasm_global_shared_data::{
+///     write_bindings, BindingsReader, NativeBindingDefinition, Shared, OUT, REQUIRED,
+/// };
+/// use ie_base::{SmallIE, M_ME_NE_1};
+/// use core::num::NonZeroU8;
+///
+/// let defs = [NativeBindingDefinition {
+///     name: "y",
+///     flags: REQUIRED,
+///     typ: 13,
+///     min_size: 1,
+///     max_size: NonZeroU8::new(1),
+///     direction: OUT,
+/// }];
+/// let mut shared = Shared::new();
+/// let y = SmallIE::TI13(M_ME_NE_1::default());
+/// write_bindings(&mut shared.latched_ports, &defs, &[&[y]]).unwrap();
+///
+/// // ... block writes its outputs ...
+///
+/// for view in shared.read_ports(&defs) {
+///     let view = view.unwrap();
+///     assert_eq!(view.name, "y");
+///     assert_eq!(view.get(0), Some(y));
+/// }
+/// ```
+pub struct BindingsReader<'a> {
+    defs: core::slice::Iter<'a, NativeBindingDefinition<'a>>,
+    header: &'a [u8],
+    payload: &'a [u8],
+    failed: bool,
+}
+
+/// Elements of a single binding, as laid out in `Shared`.
+#[derive(Debug, Clone, Copy)]
+pub struct BindingView<'a> {
+    pub name: &'a str,
+    pub direction: Direction,
+    /// `None` for `ANY` bindings.
+    pub ie_type: Option<IeType>,
+    data: &'a [u8],
+}
+
+impl<'a> BindingsReader<'a> {
+    #[must_use]
+    pub fn new(source: &'a [u8], defs: &'a [NativeBindingDefinition<'a>]) -> Self {
+        let (header, payload) = source.split_at(source.len().min(defs.len() * 2));
+        Self {
+            defs: defs.iter(),
+            header,
+            payload,
+            failed: false,
+        }
+    }
+
+    fn read(&mut self, def: &NativeBindingDefinition<'a>) -> Result<BindingView<'a>, ParseError> {
+        let [pad, bytes, rest @ ..] = self.header else {
+            return Err(ParseError::BadHeader);
+        };
+        self.header = rest;
+        let (pad, bytes) = (*pad as usize, *bytes as usize);
+        let data = self
+            .payload
+            .get(pad..pad + bytes)
+            .ok_or(ParseError::NotEnoughData)?;
+        self.payload = &self.payload[pad + bytes..];
+
+        BindingView::new(def, data).ok_or(ParseError::InvalidData)
+    }
+}
+
+impl<'a> Iterator for BindingsReader<'a> {
+    type Item = Result<BindingView<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let def = self.defs.next()?;
+        let view = self.read(def);
+        self.failed = view.is_err();
+        Some(view)
+    }
+}
+
+impl<'a> BindingView<'a> {
+    /// View of `data` holding elements of binding `def`. Returns `None` if
+    /// `def` has unknown type or `data` is not a whole number of elements.
+    #[must_use]
+    pub fn new(def: &NativeBindingDefinition<'a>, data: &'a [u8]) -> Option<Self> {
+        let ie_type = match def.typ {
+            ANY => None,
+            typ => Some(IeType::new(typ).ok()?),
+        };
+        if !data.len().is_multiple_of(element_size(ie_type)) {
+            return None;
+        }
+        Some(BindingView {
+            name: def.name,
+            direction: def.direction,
+            ie_type,
+            data,
+        })
+    }
+
+    /// Number of elements.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.data.len() / element_size(self.ie_type)
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Whether block could have written to that binding.
+    #[must_use]
+    pub fn is_output(&self) -> bool {
+        self.direction != Direction::IN
+    }
+
+    #[must_use]
+    pub fn get(&self, idx: usize) -> Option<SmallIE> {
+        let size = element_size(self.ie_type);
+        decode(self.ie_type, self.data.get(idx * size..)?)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = SmallIE> + 'a {
+        let ie_type = self.ie_type;
+        self.data
+            .chunks_exact(element_size(ie_type))
+            .filter_map(move |chunk| decode(ie_type, chunk))
+    }
+
+    /// Typed view of the elements. Returns `None` if `T` is of another type.
+    #[must_use]
+    pub fn as_slice<T: IEMeta>(&self) -> Option<&'a [T]> {
+        if Some(T::TYPECODE) != self.ie_type.map(|t| t as u8) {
+            return None;
+        }
+        <[T]>::ref_from_bytes(self.data).ok()
+    }
+
+    /// Raw bytes of the elements.
+    #[must_use]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+/// Size of an element of a binding, `IEBuf` for `ANY` bindings.
+fn element_size(ie_type: Option<IeType>) -> usize {
+    ie_type.map_or(size_of::<IEBuf>(), SmallIE::size_for_type)
+}
+
+/// Decodes an element at the start of `bytes`. Elements of `ANY` bindings
+/// that were not written are invalid and decode to `None`.
+fn decode(ie_type: Option<IeType>, bytes: &[u8]) -> Option<SmallIE> {
+    match ie_type {
+        Some(ie_type) => SmallIE::try_from_type_and_bytes(ie_type, bytes),
+        None => {
+            let buf = IEBuf::read_from_prefix(bytes).ok()?.0;
+            SmallIE::try_from(buf).ok()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use core::num::NonZeroU8;
+    use std::vec::Vec;
+
+    use ie_base::{SmallIE, M_ME_NE_1, M_SP_NA_1};
+
+    use crate::{
+        write_bindings, BindingsReader, Direction, NativeBindingDefinition, ParseError, Shared,
+        ANY, IN, IN_OUT, OUT, REQUIRED,
+    };
+
+    fn def(name: &'static str, typ: u8, direction: Direction) -> NativeBindingDefinition<'static> {
+        NativeBindingDefinition {
+            name,
+            flags: REQUIRED,
+            typ,
+            min_size: 1,
+            max_size: NonZeroU8::new(10),
+            direction,
+        }
+    }
+
+    fn ti13(value: f32) -> SmallIE {
+        SmallIE::TI13(M_ME_NE_1 {
+            value,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut shared = Shared::new();
+        let defs = [def("a", 13, IN), def("b", 1, OUT), def("c", 13, IN_OUT)];
+        let ti1 = SmallIE::TI1(M_SP_NA_1::default());
+        let elements: [&[SmallIE]; 3] = [&[ti13(1.), ti13(2.)], &[ti1; 3], &[ti13(3.)]];
+        write_bindings(&mut shared.latched_ports, &defs, &elements).unwrap();
+
+        let views: Result<Vec<_>, _> = shared.read_ports(&defs).collect();
+        let views = views.unwrap();
+        assert_eq!(views.len(), 3);
+        assert_eq!(views[0].name, "a");
+        assert!(!views[0].is_output());
+        assert_eq!(views[0].iter().collect::<Vec<_>>(), elements[0]);
+        assert_eq!(views[1].len(), 3);
+        assert_eq!(views[1].get(0), Some(ti1));
+        assert_eq!(views[1].get(3), None);
+        assert!(views[2].is_output());
+        assert_eq!(
+            views[2].as_slice::<M_ME_NE_1>().map(|s| s[0].value),
+            Some(3.)
+        );
+        assert!(views[2].as_slice::<M_SP_NA_1>().is_none());
+    }
+
+    #[test]
+    fn any_type() {
+        let mut shared = Shared::new();
+        let defs = [def("a", ANY, IN), def("b", ANY, OUT)];
+        let ti1 = SmallIE::TI1(M_SP_NA_1::default());
+        let elements: [&[SmallIE]; 2] = [&[ti13(1.), ti1], &[ti1]];
+        write_bindings(&mut shared.latched_ports, &defs, &elements).unwrap();
+
+        let views: Result<Vec<_>, _> = shared.read_ports(&defs).collect();
+        let views = views.unwrap();
+        assert_eq!(views[0].ie_type, None);
+        assert_eq!(views[0].iter().collect::<Vec<_>>(), elements[0]);
+        assert!(views[0].as_slice::<M_ME_NE_1>().is_none());
+        // Not written by the block.
+        assert_eq!(views[1].len(), 1);
+        assert_eq!(views[1].get(0), None);
+    }
+
+    #[test]
+    fn malformed() {
+        let defs = [def("a", 13, IN), def("b", 13, IN)];
+
+        let mut reader = BindingsReader::new(&[0, 5, 0, 0, 0, 0, 0], &defs[..1]);
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().is_none());
+
+        let mut reader = BindingsReader::new(&[0, 5, 0, 0], &defs[..1]);
+        assert_eq!(
+            reader.next().unwrap().err(),
+            Some(ParseError::NotEnoughData)
+        );
+        assert!(reader.next().is_none());
+
+        let mut reader = BindingsReader::new(&[0, 4, 0, 0, 0, 0], &defs[..1]);
+        assert_eq!(reader.next().unwrap().err(), Some(ParseError::InvalidData));
+
+        let mut reader = BindingsReader::new(&[0, 0, 0], &defs);
+        assert!(reader.next().unwrap().is_ok());
+        assert_eq!(reader.next().unwrap().err(), Some(ParseError::BadHeader));
+    }
+}
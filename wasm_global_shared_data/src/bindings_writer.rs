@@ -0,0 +1,242 @@
+use ie_base::{IEBuf, IeType, SmallIE};
+use ufmt::derive::uDebug;
+
+use crate::{NativeBindingDefinition, ANY, BINDINGS_BYTES_CAP, OUT};
+
+/// Errors that can occur while encoding bindings with `BindingsWriter`.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uDebug)]
+pub enum EncodeError {
+    /// Encoded bindings do not fit into `BINDINGS_BYTES_CAP` bytes,
+    /// or single binding payload exceeds 255 bytes.
+    Overflow,
+    /// Binding definition has unknown type.
+    InvalidType,
+    /// Element type doesn't match the type of the binding.
+    TypeMismatch,
+    /// Number of written bindings doesn't match the number declared.
+    BindingCount,
+}
+
+/// Encoder for the layout parsed by `BlockPorts::parse_fallible`.
+///
+/// Layout starts with a header of `[pad, bytes]` pair per binding, followed by
+/// payloads of each binding in order. Each payload is preceded by `pad` zero
+/// bytes, aligning it for its element type, and is `bytes` long.
+/// Payloads of `OUT` bindings are always zeroed. Elements of `ANY` bindings are
+/// encoded as `IEBuf`s.
+///
+/// ```rust
+/// use micrortu_wasm_global_shared_data::{BindingsWriter, NativeBindingDefinition, Shared, IN, REQUIRED};
+/// use ie_base::{SmallIE, M_ME_NE_1};
+/// use core::num::NonZeroU8;
+///
+/// let def = NativeBindingDefinition {
+///     name: "x",
+///     flags: REQUIRED,
+///     typ: 13,
+///     min_size: 1,
+///     max_size: NonZeroU8::new(1),
+///     direction: IN,
+/// };
+/// let mut shared = Shared::new();
+/// let mut writer = BindingsWriter::new(&mut shared.latched_ports, 1).unwrap();
+/// writer.push(&def, &[SmallIE::TI13(M_ME_NE_1::default())]).unwrap();
+/// assert_eq!(writer.finish(), Ok(2 + 5));
+/// ```
+pub struct BindingsWriter<'a> {
+    buffer: &'a mut [u8; BINDINGS_BYTES_CAP],
+    bindings: usize,
+    written: usize,
+    offset: usize,
+}
+
+impl<'a> BindingsWriter<'a> {
+    /// Zeroes `buffer` and reserves header for `bindings` bindings.
+    pub fn new(
+        buffer: &'a mut [u8; BINDINGS_BYTES_CAP],
+        bindings: usize,
+    ) -> Result<Self, EncodeError> {
+        let header_len = bindings * 2;
+        if header_len > BINDINGS_BYTES_CAP {
+            return Err(EncodeError::Overflow);
+        }
+        buffer.fill(0);
+        Ok(Self {
+            buffer,
+            bindings,
+            written: 0,
+            offset: header_len,
+        })
+    }
+
+    /// Appends next binding with its `elements`.
+    pub fn push(
+        &mut self,
+        def: &NativeBindingDefinition<'_>,
+        elements: &[SmallIE],
+    ) -> Result<(), EncodeError> {
+        if self.written == self.bindings {
+            return Err(EncodeError::BindingCount);
+        }
+        let (size, align) = if def.typ == ANY {
+            (size_of::<IEBuf>(), align_of::<IEBuf>())
+        } else {
+            let ie_type = IeType::new(def.typ).map_err(|_| EncodeError::InvalidType)?;
+            (
+                SmallIE::size_for_type(ie_type),
+                SmallIE::align_for_type(ie_type),
+            )
+        };
+
+        let address = self.buffer.as_ptr() as usize + self.offset;
+        let pad = address.next_multiple_of(align) - address;
+        let bytes = size * elements.len();
+        let header = [
+            u8::try_from(pad).map_err(|_| EncodeError::Overflow)?,
+            u8::try_from(bytes).map_err(|_| EncodeError::Overflow)?,
+        ];
+
+        let start = self.offset + pad;
+        let data = self
+            .buffer
+            .get_mut(start..start + bytes)
+            .ok_or(EncodeError::Overflow)?;
+        for (chunk, element) in data.chunks_exact_mut(size).zip(elements) {
+            if def.typ != ANY && element.typecode() != def.typ {
+                return Err(EncodeError::TypeMismatch);
+            }
+            if def.direction == OUT {
+                continue;
+            }
+            if def.typ == ANY {
+                chunk.copy_from_slice(&IEBuf::from(*element).0);
+            } else {
+                chunk.copy_from_slice(element.as_bytes());
+            }
+        }
+
+        self.buffer[self.written * 2..][..2].copy_from_slice(&header);
+        self.written += 1;
+        self.offset = start + bytes;
+        Ok(())
+    }
+
+    /// Checks that all declared bindings were written.
+    /// Returns the number of bytes used.
+    pub fn finish(self) -> Result<usize, EncodeError> {
+        if self.written == self.bindings {
+            Ok(self.offset)
+        } else {
+            Err(EncodeError::BindingCount)
+        }
+    }
+}
+
+/// Encodes `defs` with corresponding `elements` into `buffer`.
+/// Returns the number of bytes used.
+pub fn write_bindings(
+    buffer: &mut [u8; BINDINGS_BYTES_CAP],
+    defs: &[NativeBindingDefinition<'_>],
+    elements: &[&[SmallIE]],
+) -> Result<usize, EncodeError> {
+    if defs.len() != elements.len() {
+        return Err(EncodeError::BindingCount);
+    }
+    let mut writer = BindingsWriter::new(buffer, defs.len())?;
+    for (def, elements) in defs.iter().zip(elements) {
+        writer.push(def, elements)?;
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use core::num::NonZeroU8;
+
+    use ie_base::{SmallIE, M_ME_NE_1, M_SP_NA_1};
+
+    use super::{write_bindings, BindingsWriter, EncodeError};
+    use crate::{Direction, NativeBindingDefinition, Shared, IN, IN_OUT, OUT, REQUIRED};
+
+    fn def(typ: u8, direction: Direction) -> NativeBindingDefinition<'static> {
+        NativeBindingDefinition {
+            name: "x",
+            flags: REQUIRED,
+            typ,
+            min_size: 1,
+            max_size: NonZeroU8::new(10),
+            direction,
+        }
+    }
+
+    fn ti13(value: f32) -> SmallIE {
+        SmallIE::TI13(M_ME_NE_1 {
+            value,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn layout() {
+        let mut shared = Shared::new();
+        shared.latched_ports.fill(0xff);
+        let defs = [def(13, IN), def(1, OUT), def(13, IN_OUT)];
+        let ti1 = SmallIE::TI1(M_SP_NA_1::default());
+        let used = write_bindings(
+            &mut shared.latched_ports,
+            &defs,
+            &[&[ti13(1.), ti13(2.)], &[ti1; 3], &[]],
+        );
+        assert_eq!(used, Ok(6 + 10 + 3));
+
+        let ports = &shared.latched_ports;
+        assert_eq!(ports[..6], [0, 10, 0, 3, 0, 0]);
+        assert_eq!(ports[6..11], *ti13(1.).as_bytes());
+        assert_eq!(ports[11..16], *ti13(2.).as_bytes());
+        assert!(
+            ports[16..].iter().all(|&b| b == 0),
+            "OUT payload must be zeroed"
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let mut shared = Shared::new();
+        let defs = [def(13, IN); 2];
+        let elements = [ti13(0.); 50];
+        let result = write_bindings(&mut shared.latched_ports, &defs, &[&elements[..]; 2]);
+        assert_eq!(result, Ok(4 + 250 * 2));
+
+        let elements = [ti13(0.); 51];
+        let result = write_bindings(&mut shared.latched_ports, &defs, &[&elements[..]; 2]);
+        assert_eq!(result, Err(EncodeError::Overflow));
+
+        let elements = [ti13(0.); 52];
+        let result = write_bindings(&mut shared.latched_ports, &defs[..1], &[&elements[..]]);
+        assert_eq!(result, Err(EncodeError::Overflow));
+    }
+
+    #[test]
+    fn binding_count_and_types() {
+        let mut shared = Shared::new();
+        let mut writer = BindingsWriter::new(&mut shared.latched_ports, 1).unwrap();
+        let ti1 = SmallIE::TI1(M_SP_NA_1::default());
+        assert_eq!(
+            writer.push(&def(13, IN), &[ti1]),
+            Err(EncodeError::TypeMismatch)
+        );
+        assert_eq!(
+            writer.push(&def(42, IN), &[]),
+            Err(EncodeError::InvalidType)
+        );
+        writer.push(&def(13, IN), &[]).unwrap();
+        assert_eq!(
+            writer.push(&def(13, IN), &[]),
+            Err(EncodeError::BindingCount)
+        );
+
+        let writer = BindingsWriter::new(&mut shared.latched_ports, 2).unwrap();
+        assert_eq!(writer.finish(), Err(EncodeError::BindingCount));
+    }
+}
@@ -4,8 +4,13 @@ use core::num::NonZeroU8;
 use ufmt::derive::uDebug;
 use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
 
+pub use bindings_reader::{BindingView, BindingsReader};
+pub use bindings_writer::{write_bindings, BindingsWriter, EncodeError};
 pub use ie_base::IEBuf;
 
+mod bindings_reader;
+mod bindings_writer;
+
 pub const BINDINGS_BYTES_CAP: usize = 512;
 
 #[repr(C, align(8))]
@@ -31,12 +36,15 @@ pub struct FactoryInput {
 
 pub const REQUIRED: u8 = 0x0001;
 
+/// `typ` of a binding accepting elements of any type, passed as `IEBuf`s.
+pub const ANY: u8 = 0;
+
 /// Erorrs that can occur while parsing genarated ports from `Shared`,
 /// written by `MicroRTU`.
 /// Indicates misconfiguration of `MicroRTU` or a bug in `ports!` macro or
 /// `MicroRTU` firmware.
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, uDebug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uDebug)]
 pub enum ParseError {
     NotTerminated,
     NotEnoughData,
@@ -44,6 +52,72 @@ pub enum ParseError {
     InvalidData,
     BadHeader,
     MultiplePointsForSingular,
+    /// Binding at index `binding` has less elements than its minimum.
+    NotEnoughElements {
+        binding: u8,
+    },
+    /// Binding at index `binding` has more elements than its maximum.
+    TooManyElements {
+        binding: u8,
+    },
+    /// Elements of binding at index `binding` are malformed or of unexpected type.
+    InvalidElements {
+        binding: u8,
+    },
+    /// OUT binding at index `binding` is not zeroed.
+    NotZeroed {
+        binding: u8,
+    },
+}
+
+impl ParseError {
+    /// Number of the variant, stable across releases.
+    #[must_use]
+    pub const fn kind(self) -> u8 {
+        match self {
+            Self::NotTerminated => 0,
+            Self::NotEnoughData => 1,
+            Self::TooMuchData => 2,
+            Self::InvalidData => 3,
+            Self::BadHeader => 4,
+            Self::MultiplePointsForSingular => 5,
+            Self::NotEnoughElements { .. } => 6,
+            Self::TooManyElements { .. } => 7,
+            Self::InvalidElements { .. } => 8,
+            Self::NotZeroed { .. } => 9,
+        }
+    }
+
+    /// Index of the binding that failed to parse, if known.
+    #[must_use]
+    pub const fn binding(self) -> Option<u8> {
+        match self {
+            Self::NotEnoughElements { binding }
+            | Self::TooManyElements { binding }
+            | Self::InvalidElements { binding }
+            | Self::NotZeroed { binding } => Some(binding),
+            _ => None,
+        }
+    }
+
+    /// Inverse of [`ParseError::kind`] and [`ParseError::binding`].
+    /// Returns `None` for unknown kinds or missing binding index.
+    #[must_use]
+    pub const fn from_kind(kind: u8, binding: Option<u8>) -> Option<Self> {
+        Some(match (kind, binding) {
+            (0, None) => Self::NotTerminated,
+            (1, None) => Self::NotEnoughData,
+            (2, None) => Self::TooMuchData,
+            (3, None) => Self::InvalidData,
+            (4, None) => Self::BadHeader,
+            (5, None) => Self::MultiplePointsForSingular,
+            (6, Some(binding)) => Self::NotEnoughElements { binding },
+            (7, Some(binding)) => Self::TooManyElements { binding },
+            (8, Some(binding)) => Self::InvalidElements { binding },
+            (9, Some(binding)) => Self::NotZeroed { binding },
+            _ => return None,
+        })
+    }
 }
 
 /// A direction of a binding.
@@ -87,6 +161,21 @@ impl Shared {
             latched_ports: [0; BINDINGS_BYTES_CAP],
         }
     }
+
+    /// Decodes `latched_ports` using ports definitions of the block.
+    #[must_use]
+    pub fn read_ports<'a>(&'a self, defs: &'a [NativeBindingDefinition<'a>]) -> BindingsReader<'a> {
+        BindingsReader::new(&self.latched_ports, defs)
+    }
+
+    /// Decodes `latched_params` using params definitions of the block.
+    #[must_use]
+    pub fn read_params<'a>(
+        &'a self,
+        defs: &'a [NativeBindingDefinition<'a>],
+    ) -> BindingsReader<'a> {
+        BindingsReader::new(&self.latched_params, defs)
+    }
 }
 
 /// A binding definition.